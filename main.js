@@ -3,10 +3,11 @@ import init, { IronVeinClient } from './pkg/client.js';
 let gameClient = null;
 let connected = false;
 let inBattle = false;
-let pingStartTime = 0;
 let pingInterval = null;
 let onlinePlayers = new Map();
 let consecutiveSuccesses = 0; // Ping stability tracking
+let myUsername = '';
+const pendingChatLines = new Map(); // nonce -> element
 
 // Two-stage connection: Connect → Join Battle
 async function run() {
@@ -14,10 +15,65 @@ async function run() {
     console.log('🚀 Lightweight JS interface loaded');
     
     gameClient = new IronVeinClient();
+    subscribeToClientEvents();
+    window.addEventListener('pagehide', (e) => {
+        // Pages kept in the back/forward cache come back as they were
+        if (!e.persisted) gameClient.destroy();
+    });
     setupEventListeners();
     updateUI();
 }
 
+// Typed events from the Rust client
+function subscribeToClientEvents() {
+    gameClient.on('connection_state', onConnectionState);
+    gameClient.on('latency', onLatency);
+    gameClient.on('player_joined', upsertPlayer);
+    gameClient.on('player_updated', upsertPlayer);
+    gameClient.on('player_left', ({ username }) => {
+        onlinePlayers.delete(username);
+        updateOnlinePlayersList();
+    });
+    gameClient.on('game_state', ({ players }) => {
+        onlinePlayers = new Map(players.map(player => [player.username, player]));
+        updateOnlinePlayersList();
+        const me = onlinePlayers.get(myUsername);
+        if (me) updateStatBars(me);
+    });
+    gameClient.on('map_info', ({ width, height }) => {
+        document.getElementById('gridInfo').textContent = `${width}x${height}`;
+    });
+    gameClient.on('position', ({ x, y }) => {
+        document.getElementById('positionDisplay').textContent = `Position: (${x}, ${y})`;
+    });
+    gameClient.on('chat', onChat);
+    gameClient.on('error', ({ message }) => appendSystemMessage(`❌ Error: ${message}`));
+    gameClient.on('mode_changed', ({ mode }) => {
+        // lobby, in_battle, spectating or disconnected
+        inBattle = mode === 'in_battle';
+    });
+    gameClient.on('hotkey', ({ action }) => {
+        const chatInput = document.getElementById('chatInput');
+        if (action === 'focus_chat' && !chatInput.disabled) chatInput.focus();
+        if (action === 'leave_chat') chatInput.blur();
+        if (action === 'center_camera') syncFollowButton();
+    });
+    gameClient.on('context_action', ({ x, y, player }) => {
+        // Long-press or right-click on the map
+        const target = player && player !== myUsername ? `${player} at` : 'Cell';
+        appendSystemMessage(`🎯 ${target} (${x}, ${y})`);
+    });
+    gameClient.on('no_route', ({ x, y }) => {
+        appendSystemMessage(`🚫 No route to (${x}, ${y})`);
+    });
+    gameClient.on('outbound_discarded', ({ message_type, reason }) => {
+        // Superseded moves are routine; chat lines already show as failed
+        if (message_type === 'move' && reason !== 'superseded') {
+            appendSystemMessage(`⚠️ Queued move dropped (${reason})`);
+        }
+    });
+}
+
 function setupEventListeners() {
     // Enter key handlers
     document.getElementById('usernameInput').addEventListener('keypress', (e) => {
@@ -37,9 +93,6 @@ function setupEventListeners() {
     document.getElementById('chatInput').addEventListener('keypress', (e) => {
         if (e.key === 'Enter') sendMessage();
     });
-    
-    // Expose gameClient globally for Rust callbacks
-    window.gameClient = gameClient;
 }
 
 // Stage 1: Connect to server (see lobby, chat, players)
@@ -59,6 +112,7 @@ window.connectToServer = async function() {
     
     try {
         // Set user info and connect to server
+        myUsername = username;
         gameClient.set_user_info(username, room);
         await gameClient.connect_to_server(); // Just connect, don't join game yet
         
@@ -90,6 +144,12 @@ window.joinBattle = async function() {
     
     try {
         await gameClient.setup_game_canvas('gameCanvas');
+        // Dragging the view turns follow off inside the client
+        const canvas = document.getElementById('gameCanvas');
+        canvas.addEventListener('mouseup', syncFollowButton);
+        canvas.addEventListener('click', syncFollowButton); // minimap jumps
+        canvas.addEventListener('touchend', syncFollowButton);
+        loadSprites();
         await gameClient.join_battle(); // Spawn player in game
         
         inBattle = true;
@@ -103,6 +163,37 @@ window.joinBattle = async function() {
     }
 };
 
+// Unit sprites from ?sprites=<base>, loading <base>.png and <base>.json.
+// Units stay plain squares if the atlas is missing or broken.
+function loadSprites() {
+    const base = new URLSearchParams(window.location.search).get('sprites');
+    if (!base) return;
+    gameClient.load_sprite_atlas(`${base}.png`, `${base}.json`)
+        .catch(error => appendSystemMessage(`⚠️ Sprites unavailable: ${error}`));
+}
+
+// Camera controls - wheel, drag and pinch are handled on the canvas itself
+window.toggleCameraFollow = function() {
+    if (!gameClient) return;
+    const following = !gameClient.is_camera_following();
+    gameClient.set_camera_follow(following);
+    syncFollowButton();
+};
+
+function syncFollowButton() {
+    document.getElementById('followBtn').classList.toggle('active', gameClient.is_camera_following());
+}
+
+window.zoomCamera = function(factor) {
+    if (gameClient) gameClient.zoom_camera(factor);
+};
+
+window.resetCamera = function() {
+    if (!gameClient) return;
+    gameClient.reset_camera();
+    syncFollowButton();
+};
+
 // Send chat message (works in lobby and battle)
 window.sendMessage = function() {
     if (!connected) {
@@ -185,11 +276,10 @@ function startPingSystem() {
     const doPing = () => {
         if (connected && gameClient) {
             try {
-                pingStartTime = performance.now();
                 pingCount++;
                 
-                // Use minimal payload for efficiency
-                gameClient.send_ping(); // Will implement this as separate method
+                // Sequenced ping - RTT is measured in Rust and reported through the `latency` event
+                gameClient.send_ping();
                 
                 // Schedule next ping with adaptive interval
                 setTimeout(doPing, getPingInterval());
@@ -210,19 +300,53 @@ function startPingSystem() {
     console.log('📶 Adaptive ping system started');
 }
 
-// Ping response handler - called from Rust
-window.onPingReceived = function() {
-    if (pingStartTime > 0) {
-        const ping = Math.round(performance.now() - pingStartTime);
-        updatePingDisplay(ping);
-        pingStartTime = 0;
-        
-        // Track stability for adaptive pinging
-        if (ping < 200) {
-            consecutiveSuccesses = Math.min(consecutiveSuccesses + 1, 15);
-        } else {
-            consecutiveSuccesses = Math.max(consecutiveSuccesses - 1, 0);
+// Ping response handler - measured round trip plus rolling stats
+function onLatency({ rtt_ms: ping, stats }) {
+    updatePingDisplay(ping);
+    
+    // Track stability for adaptive pinging
+    if (ping < 200) {
+        consecutiveSuccesses = Math.min(consecutiveSuccesses + 1, 15);
+    } else {
+        consecutiveSuccesses = Math.max(consecutiveSuccesses - 1, 0);
+    }
+    
+    // Detailed stats on hover
+    const pingDisplay = document.getElementById('pingDisplay');
+    if (pingDisplay && stats.avg_ms != null) {
+        pingDisplay.title = `min ${Math.round(stats.min_ms)}ms · avg ${Math.round(stats.avg_ms)}ms · p95 ${Math.round(stats.p95_ms)}ms\n` +
+            `jitter ${Math.round(stats.jitter_ms ?? 0)}ms · loss ${(stats.packet_loss * 100).toFixed(1)}%\n` +
+            `server clock offset ${Math.round(gameClient.get_clock_offset())}ms`;
+    }
+}
+
+// Connection state changes (connecting, connected, reconnecting, disconnected)
+function onConnectionState({ state, attempt, delay_ms }) {
+    const indicator = document.querySelector('.status-indicator');
+    if (indicator) {
+        indicator.style.background = state === 'connected' ? 'var(--iron-success)'
+            : state === 'disconnected' ? 'var(--iron-accent)'
+            : 'var(--iron-warning)';
+    }
+    
+    if (state === 'connected') {
+        consecutiveSuccesses = 0;
+        if (attempt > 0) appendSystemMessage('🌐 Reconnected to server!');
+        if (!connected) {
+            // Session resumed after giving up - restart pinging
+            connected = true;
+            updateUI();
+            startPingSystem();
         }
+    } else if (state === 'reconnecting') {
+        updatePingDisplay('RECONNECTING');
+        appendSystemMessage(`🔌 Connection lost. Reconnecting in ${(delay_ms / 1000).toFixed(1)}s (attempt ${attempt})...`);
+    } else if (state === 'disconnected') {
+        if (attempt > 0) appendSystemMessage('🔌 Connection lost. Please refresh to reconnect.');
+        connected = false;
+        inBattle = false;
+        updateUI();
+        updatePingDisplay('DISCONNECTED');
     }
 }
 
@@ -230,6 +354,13 @@ function updatePingDisplay(ping) {
     const pingDisplay = document.getElementById('pingDisplay');
     if (!pingDisplay) return;
     
+    if (ping === 'RECONNECTING') {
+        pingDisplay.textContent = 'Ping: RECONNECTING';
+        pingDisplay.classList.remove('ping-good', 'ping-ok', 'ping-bad');
+        pingDisplay.classList.add('ping-ok');
+        return;
+    }
+    
     if (ping === 'DISCONNECTED') {
         pingDisplay.textContent = 'Ping: DISCONNECTED';
         pingDisplay.classList.remove('ping-good', 'ping-ok', 'ping-bad');
@@ -250,16 +381,17 @@ function updatePingDisplay(ping) {
     }
 }
 
-// Player list management - called from Rust
-window.updatePlayerInList = function(username, x, y, health, resources) {
-    onlinePlayers.set(username, { username, x, y, health, resources });
+// Player list management
+function upsertPlayer(player) {
+    onlinePlayers.set(player.username, player);
     updateOnlinePlayersList();
-};
+    if (player.username === myUsername) updateStatBars(player);
+}
 
-window.removePlayerFromList = function(username) {
-    onlinePlayers.delete(username);
-    updateOnlinePlayersList();
-};
+function updateStatBars({ health, resources }) {
+    document.getElementById('healthBar').style.width = `${Math.min(health, 100)}%`;
+    document.getElementById('resourceBar').style.width = `${Math.min(resources, 100)}%`;
+}
 
 function updateOnlinePlayersList() {
     const playersList = document.getElementById('playersList');
@@ -275,7 +407,6 @@ function updateOnlinePlayersList() {
     
     // Sort players alphabetically
     const sortedPlayers = Array.from(onlinePlayers.values()).sort((a, b) => a.username.localeCompare(b.username));
-    const myUsername = document.getElementById('userDisplay').textContent;
     
     sortedPlayers.forEach(player => {
         const playerDiv = document.createElement('div');
@@ -293,16 +424,65 @@ function updateOnlinePlayersList() {
     });
 }
 
+// Chat lines - pending lines are replaced once the server echoes their nonce
+function onChat({ username, message, timestamp, status, nonce }) {
+    const existing = nonce != null ? pendingChatLines.get(nonce) : null;
+    
+    if (status === 'pending') {
+        const messageDiv = existing || document.createElement('div');
+        messageDiv.textContent = `[${timestamp}] ${username}: ${message} [SENDING...]`;
+        messageDiv.style.opacity = '0.6';
+        messageDiv.style.fontStyle = 'italic';
+        messageDiv.style.color = '';
+        messageDiv.style.cursor = '';
+        messageDiv.onclick = null;
+        pendingChatLines.set(nonce, messageDiv);
+        if (!existing) {
+            appendChatElement(messageDiv);
+        }
+    } else if (status === 'failed') {
+        if (!existing) return;
+        existing.textContent = `[${timestamp}] ${username}: ${message} [FAILED - click to retry]`;
+        existing.style.opacity = '1';
+        existing.style.color = '#ff4444';
+        existing.style.cursor = 'pointer';
+        existing.onclick = () => {
+            try {
+                gameClient.retry_message(nonce);
+            } catch (error) {
+                appendSystemMessage(`Retry failed: ${error}`);
+            }
+        };
+    } else {
+        if (existing) {
+            existing.remove();
+            pendingChatLines.delete(nonce);
+        }
+        const messageDiv = document.createElement('div');
+        messageDiv.textContent = `[${timestamp}] ${username}: ${message}`;
+        appendChatElement(messageDiv);
+    }
+}
+
+function appendChatElement(messageDiv) {
+    const chatMessages = document.getElementById('chatMessages');
+    chatMessages.appendChild(messageDiv);
+    chatMessages.scrollTop = chatMessages.scrollHeight;
+    
+    // Limit messages to 100
+    while (chatMessages.childElementCount > 100) {
+        chatMessages.firstElementChild.remove();
+    }
+}
+
 // Simple system message helper
 function appendSystemMessage(message) {
-    const chatMessages = document.getElementById('chatMessages');
     const messageDiv = document.createElement('div');
     messageDiv.textContent = message;
     messageDiv.style.color = '#4ecdc4';
     messageDiv.style.fontStyle = 'italic';
     
-    chatMessages.appendChild(messageDiv);
-    chatMessages.scrollTop = chatMessages.scrollHeight;
+    appendChatElement(messageDiv);
 }
 
 // Start the lightweight interface
@@ -0,0 +1,75 @@
+// Host-page subscriptions for `ClientEvent`s, registered through
+// `IronVeinClient::on` / `off`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+use crate::game::events::ClientEvent;
+
+#[derive(Clone, Default)]
+pub struct EventBus {
+    listeners: Rc<RefCell<HashMap<&'static str, Vec<js_sys::Function>>>>,
+}
+
+impl EventBus {
+    pub fn on(&self, event: &str, callback: js_sys::Function) -> Result<(), JsValue> {
+        let name = Self::lookup(event)?;
+        self.listeners.borrow_mut().entry(name).or_default().push(callback);
+        Ok(())
+    }
+
+    // Removes one callback, or every callback for the event when none is given
+    pub fn off(&self, event: &str, callback: Option<&js_sys::Function>) -> Result<(), JsValue> {
+        let name = Self::lookup(event)?;
+        let mut listeners = self.listeners.borrow_mut();
+        match callback {
+            Some(callback) => {
+                if let Some(callbacks) = listeners.get_mut(name) {
+                    callbacks.retain(|registered| registered != callback);
+                }
+            }
+            None => {
+                listeners.remove(name);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        self.listeners.borrow_mut().clear();
+    }
+
+    pub fn emit(&self, event: &ClientEvent) {
+        // Snapshot so callbacks may subscribe or unsubscribe while being notified
+        let callbacks = match self.listeners.borrow().get(event.name()) {
+            Some(callbacks) if !callbacks.is_empty() => callbacks.clone(),
+            _ => return,
+        };
+
+        let payload = match serde_wasm_bindgen::to_value(event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                web_sys::console::error_1(&format!("Failed to serialize {} event: {}", event.name(), e).into());
+                return;
+            }
+        };
+
+        for callback in callbacks {
+            if let Err(e) = callback.call1(&JsValue::NULL, &payload) {
+                web_sys::console::error_2(&format!("{} listener threw:", event.name()).into(), &e);
+            }
+        }
+    }
+
+    fn lookup(event: &str) -> Result<&'static str, JsValue> {
+        ClientEvent::NAMES.iter()
+            .copied()
+            .find(|name| *name == event)
+            .ok_or_else(|| JsValue::from_str(&format!(
+                "Unknown event '{}'. Expected one of: {}", event, ClientEvent::NAMES.join(", ")
+            )))
+    }
+}
@@ -0,0 +1,211 @@
+// Camera over the world: world pixels <-> canvas pixels, pan, zoom and follow.
+
+use super::grid::{MapSize, CELL_SIZE};
+
+pub const MIN_ZOOM: f64 = 0.25;
+pub const MAX_ZOOM: f64 = 4.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    // World pixel at the centre of the viewport
+    pub center_x: f64,
+    pub center_y: f64,
+    // Canvas pixels per world pixel
+    pub zoom: f64,
+    pub viewport_width: f64,
+    pub viewport_height: f64,
+    pub world_width: f64,
+    pub world_height: f64,
+    // Keep the local player centred until the user pans away
+    pub follow: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        let map = MapSize::default();
+        Self::new(map.world_width(), map.world_height())
+    }
+}
+
+impl Camera {
+    pub fn new(world_width: f64, world_height: f64) -> Self {
+        Self {
+            center_x: world_width / 2.0,
+            center_y: world_height / 2.0,
+            zoom: 1.0,
+            viewport_width: world_width,
+            viewport_height: world_height,
+            world_width,
+            world_height,
+            follow: false,
+        }
+    }
+
+    pub fn set_viewport(&mut self, width: f64, height: f64) {
+        self.viewport_width = width.max(1.0);
+        self.viewport_height = height.max(1.0);
+        self.clamp();
+    }
+
+    // Adopts a new world size, refitting the view when it actually changed
+    pub fn set_world(&mut self, world_width: f64, world_height: f64) {
+        if world_width == self.world_width && world_height == self.world_height {
+            return;
+        }
+        self.world_width = world_width;
+        self.world_height = world_height;
+        self.fit();
+    }
+
+    pub fn world_to_screen(&self, world_x: f64, world_y: f64) -> (f64, f64) {
+        (
+            (world_x - self.center_x) * self.zoom + self.viewport_width / 2.0,
+            (world_y - self.center_y) * self.zoom + self.viewport_height / 2.0,
+        )
+    }
+
+    pub fn screen_to_world(&self, screen_x: f64, screen_y: f64) -> (f64, f64) {
+        (
+            (screen_x - self.viewport_width / 2.0) / self.zoom + self.center_x,
+            (screen_y - self.viewport_height / 2.0) / self.zoom + self.center_y,
+        )
+    }
+
+    // Grid cell under a canvas pixel, if it falls inside the world
+    pub fn screen_to_cell(&self, screen_x: f64, screen_y: f64) -> Option<(u32, u32)> {
+        let (world_x, world_y) = self.screen_to_world(screen_x, screen_y);
+        if world_x < 0.0 || world_y < 0.0 || world_x >= self.world_width || world_y >= self.world_height {
+            return None;
+        }
+        Some(((world_x / CELL_SIZE as f64) as u32, (world_y / CELL_SIZE as f64) as u32))
+    }
+
+    // Affine transform for the 2d context: [a, b, c, d, e, f]
+    pub fn transform(&self) -> [f64; 6] {
+        let (offset_x, offset_y) = self.world_to_screen(0.0, 0.0);
+        [self.zoom, 0.0, 0.0, self.zoom, offset_x, offset_y]
+    }
+
+    // Drags the world by a canvas-pixel delta; manual panning stops following
+    pub fn pan_by(&mut self, delta_x: f64, delta_y: f64) {
+        self.center_x -= delta_x / self.zoom;
+        self.center_y -= delta_y / self.zoom;
+        self.follow = false;
+        self.clamp();
+    }
+
+    // Zooms by a factor while keeping the world point under (screen_x, screen_y) fixed
+    pub fn zoom_at(&mut self, factor: f64, screen_x: f64, screen_y: f64) {
+        let (world_x, world_y) = self.screen_to_world(screen_x, screen_y);
+        self.zoom = (self.zoom * factor).clamp(self.min_zoom(), MAX_ZOOM);
+        self.center_x = world_x - (screen_x - self.viewport_width / 2.0) / self.zoom;
+        self.center_y = world_y - (screen_y - self.viewport_height / 2.0) / self.zoom;
+        self.clamp();
+    }
+
+    pub fn center_on(&mut self, world_x: f64, world_y: f64) {
+        self.center_x = world_x;
+        self.center_y = world_y;
+        self.clamp();
+    }
+
+    // Zoom that shows the whole world, centred
+    pub fn fit(&mut self) {
+        self.zoom = self.fit_zoom().clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center_x = self.world_width / 2.0;
+        self.center_y = self.world_height / 2.0;
+    }
+
+    fn fit_zoom(&self) -> f64 {
+        (self.viewport_width / self.world_width).min(self.viewport_height / self.world_height)
+    }
+
+    // Never zoom out further than needed to see the whole world
+    fn min_zoom(&self) -> f64 {
+        self.fit_zoom().clamp(MIN_ZOOM, MAX_ZOOM)
+    }
+
+    // Keeps the world covering the viewport, or centred when it is smaller
+    fn clamp(&mut self) {
+        let half_width = self.viewport_width / 2.0 / self.zoom;
+        let half_height = self.viewport_height / 2.0 / self.zoom;
+
+        self.center_x = if half_width * 2.0 >= self.world_width {
+            self.world_width / 2.0
+        } else {
+            self.center_x.clamp(half_width, self.world_width - half_width)
+        };
+        self.center_y = if half_height * 2.0 >= self.world_height {
+            self.world_height / 2.0
+        } else {
+            self.center_y.clamp(half_height, self.world_height - half_height)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::grid::cell_center;
+
+    // 64x64 map (1024x1024 world pixels) in an 800x600 canvas
+    fn camera() -> Camera {
+        let mut camera = Camera::default();
+        camera.set_viewport(800.0, 600.0);
+        camera.fit();
+        camera
+    }
+
+    #[test]
+    fn world_and_screen_round_trip() {
+        let mut camera = camera();
+        camera.zoom_at(2.0, 123.0, 456.0);
+        camera.pan_by(-37.0, 12.0);
+
+        let (screen_x, screen_y) = camera.world_to_screen(300.0, 500.0);
+        let (world_x, world_y) = camera.screen_to_world(screen_x, screen_y);
+        assert!((world_x - 300.0).abs() < 1e-9);
+        assert!((world_y - 500.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn screen_to_cell_finds_the_cell_under_its_centre() {
+        let mut camera = camera();
+        camera.zoom_at(1.5, 400.0, 300.0);
+        for (x, y) in [(0, 0), (10, 20), (63, 63)] {
+            let (world_x, world_y) = cell_center(x, y);
+            camera.center_on(world_x, world_y);
+            let (screen_x, screen_y) = camera.world_to_screen(world_x, world_y);
+            assert_eq!(camera.screen_to_cell(screen_x, screen_y), Some((x, y)));
+        }
+    }
+
+    #[test]
+    fn screen_to_cell_outside_the_world_is_none() {
+        let camera = camera();
+        // Fitted: the 1024px world is shown 600px tall, centred with margins on both sides
+        let (left, top) = camera.world_to_screen(0.0, 0.0);
+        let (right, bottom) = camera.world_to_screen(1024.0, 1024.0);
+        assert_eq!(camera.screen_to_cell(left - 1.0, 300.0), None);
+        assert_eq!(camera.screen_to_cell(right + 1.0, 300.0), None);
+        assert_eq!(camera.screen_to_cell(400.0, top - 1.0), None);
+        assert_eq!(camera.screen_to_cell(400.0, bottom), None);
+        assert_eq!(camera.screen_to_cell(left + 0.5, top + 0.5), Some((0, 0)));
+    }
+
+    #[test]
+    fn zoom_keeps_the_point_under_the_cursor() {
+        let mut camera = camera();
+        let before = camera.screen_to_world(250.0, 150.0);
+        camera.zoom_at(2.0, 250.0, 150.0);
+        let after = camera.screen_to_world(250.0, 150.0);
+        assert!((before.0 - after.0).abs() < 1e-9 && (before.1 - after.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn zoom_never_goes_below_fitting_the_world() {
+        let mut camera = camera();
+        camera.zoom_at(0.01, 400.0, 300.0);
+        assert!((camera.zoom - 600.0 / 1024.0).abs() < 1e-9);
+    }
+}
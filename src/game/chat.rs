@@ -0,0 +1,170 @@
+// Optimistic chat lines awaiting the server echo, keyed by client nonce.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+// How long an unacknowledged line stays pending before it is marked failed
+pub const CHAT_ACK_TIMEOUT_MS: f64 = 10_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatStatus {
+    Pending,
+    Sent,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingChat {
+    // Who sent it; only their echo confirms the line
+    pub username: String,
+    pub message: String,
+    pub timestamp: String,
+    // None while the line is still waiting in the outbox
+    pub sent_at: Option<f64>,
+    pub failed: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct PendingChats {
+    entries: HashMap<String, PendingChat>,
+}
+
+impl PendingChats {
+    pub fn insert(&mut self, nonce: &str, username: &str, message: &str, timestamp: &str) {
+        self.entries.insert(nonce.to_string(), PendingChat {
+            username: username.to_string(),
+            message: message.to_string(),
+            timestamp: timestamp.to_string(),
+            sent_at: None,
+            failed: false,
+        });
+    }
+
+    // Starts the ack timeout once the line actually went out
+    pub fn mark_sent(&mut self, nonce: &str, now_ms: f64) {
+        if let Some(pending) = self.entries.get_mut(nonce) {
+            pending.sent_at = Some(now_ms);
+        }
+    }
+
+    pub fn remove(&mut self, nonce: &str) -> Option<PendingChat> {
+        self.entries.remove(nonce)
+    }
+
+    // True when the echo matches one of our lines; late echoes of failed lines still count.
+    // Another player's line that happens to carry the same nonce does not.
+    pub fn confirm(&mut self, nonce: &str, username: &str) -> bool {
+        let ours = self.entries.get(nonce).is_some_and(|pending| pending.username == username);
+        ours && self.remove(nonce).is_some()
+    }
+
+    // Marks a line failed without waiting for the timeout, e.g. when it was never sent
+    pub fn fail(&mut self, nonce: &str) -> Option<PendingChat> {
+        let pending = self.entries.get_mut(nonce).filter(|pending| !pending.failed)?;
+        pending.failed = true;
+        Some(pending.clone())
+    }
+
+    // Marks lines older than the ack timeout as failed and returns the newly failed ones
+    pub fn expire(&mut self, now_ms: f64) -> Vec<(String, PendingChat)> {
+        let mut failed: Vec<(String, PendingChat)> = self.entries.iter_mut()
+            .filter(|(_, pending)| {
+                !pending.failed && pending.sent_at.is_some_and(|sent_at| now_ms - sent_at >= CHAT_ACK_TIMEOUT_MS)
+            })
+            .map(|(nonce, pending)| {
+                pending.failed = true;
+                (nonce.clone(), pending.clone())
+            })
+            .collect();
+        failed.sort_by(|a, b| a.1.sent_at.unwrap_or(0.0).total_cmp(&b.1.sent_at.unwrap_or(0.0)));
+        failed
+    }
+
+    // Puts a failed line back in flight under the same nonce
+    pub fn retry(&mut self, nonce: &str) -> Option<PendingChat> {
+        let pending = self.entries.get_mut(nonce).filter(|pending| pending.failed)?;
+        pending.failed = false;
+        pending.sent_at = None;
+        Some(pending.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chats(lines: &[(&str, &str)]) -> PendingChats {
+        let mut chats = PendingChats::default();
+        for (nonce, message) in lines {
+            chats.insert(nonce, "ann", message, "12:00:00");
+        }
+        chats
+    }
+
+    #[test]
+    fn identical_texts_are_confirmed_by_nonce() {
+        let mut chats = chats(&[("a", "gg"), ("b", "gg")]);
+        assert!(chats.confirm("b", "ann"));
+        assert!(!chats.confirm("b", "ann"));
+        // The first line is still waiting for its own echo
+        assert!(chats.remove("a").is_some());
+    }
+
+    #[test]
+    fn another_players_echo_does_not_confirm_ours() {
+        let mut chats = chats(&[("a", "hi")]);
+        assert!(!chats.confirm("a", "bob"));
+        assert!(!chats.confirm("zzz", "ann"));
+        assert!(chats.confirm("a", "ann"));
+    }
+
+    #[test]
+    fn timeout_starts_when_the_line_is_sent() {
+        let mut chats = chats(&[("a", "hi")]);
+        // Still in the outbox: never times out
+        assert!(chats.expire(CHAT_ACK_TIMEOUT_MS * 10.0).is_empty());
+
+        chats.mark_sent("a", 1_000.0);
+        assert!(chats.expire(1_000.0 + CHAT_ACK_TIMEOUT_MS - 1.0).is_empty());
+        let failed = chats.expire(1_000.0 + CHAT_ACK_TIMEOUT_MS);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "a");
+        assert!(failed[0].1.failed);
+        // Reported once
+        assert!(chats.expire(1_000.0 + CHAT_ACK_TIMEOUT_MS * 2.0).is_empty());
+    }
+
+    #[test]
+    fn expired_lines_come_back_oldest_first() {
+        let mut chats = chats(&[("a", "one"), ("b", "two"), ("c", "three")]);
+        chats.mark_sent("b", 0.0);
+        chats.mark_sent("c", 10.0);
+        chats.mark_sent("a", 20.0);
+        let failed: Vec<String> = chats.expire(CHAT_ACK_TIMEOUT_MS * 2.0).into_iter().map(|(nonce, _)| nonce).collect();
+        assert_eq!(failed, ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn failed_lines_can_be_retried() {
+        let mut chats = chats(&[("a", "hi")]);
+        assert!(chats.retry("a").is_none());
+
+        assert_eq!(chats.fail("a").map(|pending| pending.message), Some("hi".to_string()));
+        assert!(chats.fail("a").is_none());
+
+        let retried = chats.retry("a").unwrap();
+        assert!(!retried.failed);
+        assert_eq!(retried.sent_at, None);
+        chats.mark_sent("a", 0.0);
+        assert_eq!(chats.expire(CHAT_ACK_TIMEOUT_MS).len(), 1);
+    }
+
+    #[test]
+    fn late_echo_of_a_failed_line_still_confirms_it() {
+        let mut chats = chats(&[("a", "hi")]);
+        chats.fail("a");
+        assert!(chats.confirm("a", "ann"));
+    }
+}
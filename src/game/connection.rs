@@ -0,0 +1,193 @@
+// Connection lifecycle and endpoint helpers shared by the WebSocket adapter.
+
+use serde::Serialize;
+
+// Connection lifecycle reported to the host page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Connected,
+    Reconnecting,
+}
+
+impl ConnectionState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionState::Disconnected => "disconnected",
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Connected => "connected",
+            ConnectionState::Reconnecting => "reconnecting",
+        }
+    }
+}
+
+// A connection that stayed up this long counts as healthy even if the
+// server never answered anything
+pub const STABLE_CONNECTION_MS: f64 = 10_000.0;
+
+// Reconnect state machine - exponential backoff with jitter. Opening a socket
+// does not reset the backoff; only a server that answers, or a connection that
+// stays up, does. Otherwise a server that accepts and then drops us straight
+// away would be retried at the shortest delay forever.
+#[derive(Debug)]
+pub struct ReconnectState {
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub base_delay_ms: u32,
+    pub max_delay_ms: u32,
+    pub timer_id: Option<i32>,
+    pub enabled: bool,
+    connected_at: Option<f64>,
+}
+
+impl Default for ReconnectState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReconnectState {
+    pub fn new() -> Self {
+        Self {
+            attempt: 0,
+            max_attempts: 10,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+            timer_id: None,
+            enabled: false,
+            connected_at: None,
+        }
+    }
+
+    // The socket opened; returns the attempts it took, 0 for a first connect
+    pub fn opened(&mut self, now: f64) -> u32 {
+        self.connected_at = Some(now);
+        self.attempt
+    }
+
+    // The server answered, so the next drop starts backing off from scratch
+    pub fn confirmed(&mut self) {
+        self.attempt = 0;
+    }
+
+    // The socket closed; a connection that lasted counts as confirmed
+    pub fn closed(&mut self, now: f64) {
+        if self.connected_at.take().is_some_and(|at| now - at >= STABLE_CONNECTION_MS) {
+            self.attempt = 0;
+        }
+    }
+
+    pub fn exhausted(&self) -> bool {
+        self.attempt >= self.max_attempts
+    }
+
+    // Delay before the next attempt: half the capped exponential delay plus
+    // a random share of the other half, so clients don't reconnect in lockstep
+    pub fn next_delay_ms(&self, jitter: f64) -> u32 {
+        let exponential = self.base_delay_ms.saturating_mul(1u32 << self.attempt.min(16));
+        let capped = exponential.min(self.max_delay_ms) as f64;
+        (capped / 2.0 + capped / 2.0 * jitter.clamp(0.0, 1.0)) as u32
+    }
+}
+
+// Accepts ws(s)://, http(s):// or bare host[:port] and returns a WebSocket base URL.
+// Bare hosts follow the page protocol so https pages never attempt insecure sockets.
+pub fn normalize_server_url(server_url: &str, page_is_secure: bool) -> String {
+    let server_url = server_url.trim().trim_end_matches('/');
+    let (scheme, rest) = match server_url.split_once("://") {
+        Some(("https", rest)) | Some(("wss", rest)) => ("wss", rest),
+        Some(("http", rest)) | Some(("ws", rest)) => ("ws", rest),
+        Some((_, rest)) => (if page_is_secure { "wss" } else { "ws" }, rest),
+        None => (if page_is_secure { "wss" } else { "ws" }, server_url),
+    };
+    format!("{}://{}", scheme, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reconnect(attempt: u32) -> ReconnectState {
+        ReconnectState { attempt, ..ReconnectState::new() }
+    }
+
+    #[test]
+    fn delay_doubles_from_the_base() {
+        assert_eq!(reconnect(0).next_delay_ms(1.0), 500);
+        assert_eq!(reconnect(1).next_delay_ms(1.0), 1_000);
+        assert_eq!(reconnect(3).next_delay_ms(1.0), 4_000);
+    }
+
+    #[test]
+    fn delay_is_capped_even_for_huge_attempt_counts() {
+        assert_eq!(reconnect(6).next_delay_ms(1.0), 30_000);
+        assert_eq!(reconnect(u32::MAX).next_delay_ms(1.0), 30_000);
+        let state = ReconnectState { base_delay_ms: u32::MAX, ..reconnect(16) };
+        assert_eq!(state.next_delay_ms(1.0), 30_000);
+    }
+
+    #[test]
+    fn jitter_stays_within_the_upper_half() {
+        let state = reconnect(2);
+        assert_eq!(state.next_delay_ms(0.0), 1_000);
+        assert_eq!(state.next_delay_ms(0.5), 1_500);
+        assert_eq!(state.next_delay_ms(1.0), 2_000);
+        // Out-of-range randomness is clamped
+        assert_eq!(state.next_delay_ms(-3.0), 1_000);
+        assert_eq!(state.next_delay_ms(7.0), 2_000);
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        assert!(!reconnect(9).exhausted());
+        assert!(reconnect(10).exhausted());
+        assert!(ReconnectState { max_attempts: 0, ..reconnect(0) }.exhausted());
+    }
+
+    #[test]
+    fn opening_alone_does_not_reset_the_backoff() {
+        let mut state = reconnect(3);
+        assert_eq!(state.opened(0.0), 3);
+        state.closed(200.0);
+        assert_eq!(state.attempt, 3);
+    }
+
+    #[test]
+    fn an_answer_or_a_lasting_connection_resets_the_backoff() {
+        let mut state = reconnect(3);
+        state.opened(0.0);
+        state.confirmed();
+        assert_eq!(state.attempt, 0);
+
+        let mut state = reconnect(3);
+        state.opened(0.0);
+        state.closed(STABLE_CONNECTION_MS);
+        assert_eq!(state.attempt, 0);
+    }
+
+    #[test]
+    fn bare_hosts_follow_the_page_protocol() {
+        assert_eq!(normalize_server_url("localhost:8080", false), "ws://localhost:8080");
+        assert_eq!(normalize_server_url("localhost:8080", true), "wss://localhost:8080");
+        assert_eq!(normalize_server_url("  example.com/ ", true), "wss://example.com");
+    }
+
+    #[test]
+    fn explicit_schemes_map_to_websocket_schemes() {
+        assert_eq!(normalize_server_url("https://example.com", false), "wss://example.com");
+        assert_eq!(normalize_server_url("wss://example.com/", false), "wss://example.com");
+        assert_eq!(normalize_server_url("http://example.com", true), "ws://example.com");
+        assert_eq!(normalize_server_url("ws://127.0.0.1:8080", true), "ws://127.0.0.1:8080");
+        // Unknown schemes are replaced
+        assert_eq!(normalize_server_url("ftp://example.com", true), "wss://example.com");
+    }
+
+    #[test]
+    fn server_parameter_examples_resolve() {
+        // The forms the README suggests for ?server=
+        assert_eq!(normalize_server_url("localhost:8080", false), "ws://localhost:8080");
+        assert_eq!(normalize_server_url("wss://game.example.com:9443", false), "wss://game.example.com:9443");
+    }
+}
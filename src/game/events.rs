@@ -0,0 +1,89 @@
+// Typed events emitted to host-page subscribers. Each event serializes to a
+// plain object whose `type` field matches the name it is subscribed under.
+
+use serde::Serialize;
+
+use super::chat::ChatStatus;
+use super::connection::ConnectionState;
+use super::grid::MapSize;
+use super::keymap::Action;
+use super::latency::LatencyStats;
+use super::mode::ClientMode;
+use super::outbox::DiscardReason;
+use super::protocol::Player;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientEvent {
+    PlayerJoined(Player),
+    PlayerUpdated(Player),
+    PlayerLeft { username: String },
+    GameState { players: Vec<Player> },
+    // Map dimensions announced by the server, in cells
+    MapInfo(MapSize),
+    // Position of the local unit; unconfirmed until the server echoes it
+    Position { x: u32, y: u32, confirmed: bool },
+    Chat {
+        username: String,
+        message: String,
+        timestamp: String,
+        status: ChatStatus,
+        // Set for our own lines so pending, sent and failed updates can be matched
+        nonce: Option<String>,
+    },
+    Error { message: String },
+    // `attempt` is the reconnect attempt in progress, or the number of
+    // attempts it took to reconnect / give up
+    ConnectionState { state: ConnectionState, attempt: u32, delay_ms: Option<u32> },
+    Latency { rtt_ms: f64, stats: LatencyStats },
+    // A queued outbound message that will never be sent
+    OutboundDiscarded { message_type: String, reason: DiscardReason, nonce: Option<String> },
+    ModeChanged { mode: ClientMode },
+    // A bound key for something the host page owns, e.g. focusing the chat box
+    Hotkey { action: Action },
+    // Long-press or right-click on a cell, with the player standing there if any
+    ContextAction { x: u32, y: u32, player: Option<String> },
+    // No route found to a clicked cell; the move went out without waypoints
+    // so the server can still route it
+    NoRoute { x: u32, y: u32 },
+}
+
+impl ClientEvent {
+    pub const NAMES: &'static [&'static str] = &[
+        "player_joined",
+        "player_updated",
+        "player_left",
+        "game_state",
+        "map_info",
+        "position",
+        "chat",
+        "error",
+        "connection_state",
+        "latency",
+        "outbound_discarded",
+        "mode_changed",
+        "hotkey",
+        "context_action",
+        "no_route",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClientEvent::PlayerJoined(_) => "player_joined",
+            ClientEvent::PlayerUpdated(_) => "player_updated",
+            ClientEvent::PlayerLeft { .. } => "player_left",
+            ClientEvent::GameState { .. } => "game_state",
+            ClientEvent::MapInfo(_) => "map_info",
+            ClientEvent::Position { .. } => "position",
+            ClientEvent::Chat { .. } => "chat",
+            ClientEvent::Error { .. } => "error",
+            ClientEvent::ConnectionState { .. } => "connection_state",
+            ClientEvent::Latency { .. } => "latency",
+            ClientEvent::OutboundDiscarded { .. } => "outbound_discarded",
+            ClientEvent::ModeChanged { .. } => "mode_changed",
+            ClientEvent::Hotkey { .. } => "hotkey",
+            ClientEvent::ContextAction { .. } => "context_action",
+            ClientEvent::NoRoute { .. } => "no_route",
+        }
+    }
+}
@@ -0,0 +1,273 @@
+// Touch gesture recognition: tap, long-press, one- or two-finger pan and
+// pinch zoom. Fed with canvas-pixel touch points; the adapter turns the
+// resulting gestures into camera changes, move orders and context actions.
+
+// A touch that moves less than this is still a tap (fingers are less precise than a mouse)
+pub const TAP_SLOP_PX: f64 = 10.0;
+pub const TAP_MAX_MS: f64 = 300.0;
+pub const LONG_PRESS_MS: f64 = 500.0;
+// A second tap this soon after the last accepted one is treated as accidental
+pub const DOUBLE_TAP_MS: f64 = 350.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TouchPhase {
+    Start,
+    Move,
+    End,
+    Cancel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    Tap { x: f64, y: f64 },
+    LongPress { x: f64, y: f64 },
+    // Canvas-pixel drag delta
+    Pan { dx: f64, dy: f64 },
+    // Zoom factor around a canvas point
+    Pinch { factor: f64, x: f64, y: f64 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Press {
+    start_x: f64,
+    start_y: f64,
+    started_at: f64,
+    last_x: f64,
+    last_y: f64,
+    // Moved past the slop, so it is a pan rather than a tap or long-press
+    moved: bool,
+    long_pressed: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pinch {
+    mid_x: f64,
+    mid_y: f64,
+    distance: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct GestureTracker {
+    press: Option<Press>,
+    pinch: Option<Pinch>,
+    // A second finger landed; this touch sequence is no longer a tap or long-press
+    multi_touch: bool,
+    last_tap_at: Option<f64>,
+}
+
+impl GestureTracker {
+    // `points` are the touches still down after the event, at most two are used
+    pub fn touch(&mut self, phase: TouchPhase, points: &[(f64, f64)], now: f64) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+
+        match points {
+            [] => {
+                if phase == TouchPhase::End {
+                    gestures.extend(self.finish_tap(now));
+                }
+                self.press = None;
+                self.pinch = None;
+                self.multi_touch = false;
+            }
+            &[(x, y)] => {
+                if self.pinch.take().is_some() {
+                    // One finger lifted off a pinch: keep panning from here without a jump
+                    if let Some(press) = self.press.as_mut() {
+                        press.last_x = x;
+                        press.last_y = y;
+                    }
+                }
+
+                match self.press.as_mut() {
+                    None => {
+                        self.press = Some(Press {
+                            start_x: x,
+                            start_y: y,
+                            started_at: now,
+                            last_x: x,
+                            last_y: y,
+                            // Left over from a two-finger gesture: pan only
+                            moved: self.multi_touch,
+                            long_pressed: false,
+                        });
+                    }
+                    Some(press) => {
+                        let (dx, dy) = (x - press.last_x, y - press.last_y);
+                        press.last_x = x;
+                        press.last_y = y;
+                        if !press.moved && (x - press.start_x).hypot(y - press.start_y) > TAP_SLOP_PX {
+                            press.moved = true;
+                        }
+                        if press.moved && (dx, dy) != (0.0, 0.0) {
+                            gestures.push(Gesture::Pan { dx, dy });
+                        }
+                    }
+                }
+            }
+            &[(x1, y1), (x2, y2), ..] => {
+                self.multi_touch = true;
+                let current = Pinch {
+                    mid_x: (x1 + x2) / 2.0,
+                    mid_y: (y1 + y2) / 2.0,
+                    distance: (x2 - x1).hypot(y2 - y1),
+                };
+                if let Some(previous) = self.pinch {
+                    if previous.distance > 0.0 {
+                        gestures.push(Gesture::Pinch {
+                            factor: current.distance / previous.distance,
+                            x: current.mid_x,
+                            y: current.mid_y,
+                        });
+                    }
+                    gestures.push(Gesture::Pan {
+                        dx: current.mid_x - previous.mid_x,
+                        dy: current.mid_y - previous.mid_y,
+                    });
+                }
+                self.pinch = Some(current);
+                // Any single finger left afterwards pans, never taps
+                if let Some(press) = self.press.as_mut() {
+                    press.moved = true;
+                }
+            }
+        }
+
+        gestures
+    }
+
+    // Checked from a timer: fires once when a still, single touch has been held long enough
+    pub fn poll_long_press(&mut self, now: f64) -> Option<Gesture> {
+        if self.multi_touch {
+            return None;
+        }
+        let press = self.press.as_mut()?;
+        if press.moved || press.long_pressed || now - press.started_at < LONG_PRESS_MS {
+            return None;
+        }
+        press.long_pressed = true;
+        Some(Gesture::LongPress { x: press.start_x, y: press.start_y })
+    }
+
+    fn finish_tap(&mut self, now: f64) -> Option<Gesture> {
+        let press = self.press?;
+        if self.multi_touch || press.moved || press.long_pressed || now - press.started_at > TAP_MAX_MS {
+            return None;
+        }
+        if self.last_tap_at.is_some_and(|last| now - last < DOUBLE_TAP_MS) {
+            return None;
+        }
+        self.last_tap_at = Some(now);
+        Some(Gesture::Tap { x: press.start_x, y: press.start_y })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TouchPhase::{End, Move, Start};
+
+    // A single finger pressed at (x, y) at `at` and lifted at `until`
+    fn tap(tracker: &mut GestureTracker, x: f64, y: f64, at: f64, until: f64) -> Vec<Gesture> {
+        tracker.touch(Start, &[(x, y)], at);
+        tracker.touch(End, &[], until)
+    }
+
+    #[test]
+    fn quick_still_touch_is_a_tap() {
+        let mut tracker = GestureTracker::default();
+        assert_eq!(tap(&mut tracker, 50.0, 60.0, 0.0, 100.0), [Gesture::Tap { x: 50.0, y: 60.0 }]);
+    }
+
+    #[test]
+    fn wobble_within_the_slop_is_still_a_tap() {
+        let mut tracker = GestureTracker::default();
+        tracker.touch(Start, &[(50.0, 50.0)], 0.0);
+        assert!(tracker.touch(Move, &[(56.0, 58.0)], 50.0).is_empty());
+        assert_eq!(tracker.touch(End, &[], 100.0), [Gesture::Tap { x: 50.0, y: 50.0 }]);
+    }
+
+    #[test]
+    fn moved_press_pans_instead_of_tapping() {
+        let mut tracker = GestureTracker::default();
+        tracker.touch(Start, &[(50.0, 50.0)], 0.0);
+        assert_eq!(tracker.touch(Move, &[(65.0, 50.0)], 50.0), [Gesture::Pan { dx: 15.0, dy: 0.0 }]);
+        assert_eq!(tracker.touch(Move, &[(65.0, 45.0)], 60.0), [Gesture::Pan { dx: 0.0, dy: -5.0 }]);
+        // Coming back does not make it a tap again
+        tracker.touch(Move, &[(50.0, 50.0)], 70.0);
+        assert!(tracker.touch(End, &[], 100.0).is_empty());
+    }
+
+    #[test]
+    fn slow_release_is_not_a_tap() {
+        let mut tracker = GestureTracker::default();
+        assert!(tap(&mut tracker, 50.0, 50.0, 0.0, TAP_MAX_MS + 1.0).is_empty());
+    }
+
+    #[test]
+    fn long_press_fires_once_past_the_threshold() {
+        let mut tracker = GestureTracker::default();
+        tracker.touch(Start, &[(20.0, 30.0)], 0.0);
+        assert_eq!(tracker.poll_long_press(LONG_PRESS_MS - 1.0), None);
+        assert_eq!(tracker.poll_long_press(LONG_PRESS_MS), Some(Gesture::LongPress { x: 20.0, y: 30.0 }));
+        assert_eq!(tracker.poll_long_press(LONG_PRESS_MS + 100.0), None);
+        // Lifting afterwards is not also a tap
+        assert!(tracker.touch(End, &[], LONG_PRESS_MS + 200.0).is_empty());
+    }
+
+    #[test]
+    fn moved_press_never_becomes_a_long_press() {
+        let mut tracker = GestureTracker::default();
+        tracker.touch(Start, &[(20.0, 30.0)], 0.0);
+        tracker.touch(Move, &[(40.0, 30.0)], 100.0);
+        assert_eq!(tracker.poll_long_press(LONG_PRESS_MS * 2.0), None);
+    }
+
+    #[test]
+    fn second_finger_cancels_the_tap() {
+        let mut tracker = GestureTracker::default();
+        tracker.touch(Start, &[(50.0, 50.0)], 0.0);
+        tracker.touch(Start, &[(50.0, 50.0), (100.0, 50.0)], 20.0);
+        tracker.touch(End, &[(50.0, 50.0)], 40.0);
+        assert_eq!(tracker.poll_long_press(LONG_PRESS_MS), None);
+        assert!(tracker.touch(End, &[], 60.0).is_empty());
+
+        // The next touch sequence starts fresh
+        assert_eq!(tap(&mut tracker, 10.0, 10.0, 1_000.0, 1_050.0), [Gesture::Tap { x: 10.0, y: 10.0 }]);
+    }
+
+    #[test]
+    fn pinch_scales_by_the_change_in_finger_distance() {
+        let mut tracker = GestureTracker::default();
+        assert!(tracker.touch(Start, &[(100.0, 100.0), (200.0, 100.0)], 0.0).is_empty());
+
+        let gestures = tracker.touch(Move, &[(50.0, 100.0), (250.0, 100.0)], 16.0);
+        assert_eq!(gestures, [
+            Gesture::Pinch { factor: 2.0, x: 150.0, y: 100.0 },
+            Gesture::Pan { dx: 0.0, dy: 0.0 },
+        ]);
+
+        // Both fingers sliding together pans by the midpoint
+        let gestures = tracker.touch(Move, &[(60.0, 110.0), (260.0, 110.0)], 32.0);
+        assert_eq!(gestures, [
+            Gesture::Pinch { factor: 1.0, x: 160.0, y: 110.0 },
+            Gesture::Pan { dx: 10.0, dy: 10.0 },
+        ]);
+    }
+
+    #[test]
+    fn lifting_one_pinch_finger_pans_without_a_jump() {
+        let mut tracker = GestureTracker::default();
+        tracker.touch(Start, &[(100.0, 100.0)], 0.0);
+        tracker.touch(Start, &[(100.0, 100.0), (200.0, 100.0)], 10.0);
+        tracker.touch(End, &[(200.0, 100.0)], 20.0);
+        assert_eq!(tracker.touch(Move, &[(205.0, 100.0)], 30.0), [Gesture::Pan { dx: 5.0, dy: 0.0 }]);
+    }
+
+    #[test]
+    fn second_tap_inside_the_debounce_window_is_dropped() {
+        let mut tracker = GestureTracker::default();
+        assert_eq!(tap(&mut tracker, 50.0, 50.0, 0.0, 50.0).len(), 1);
+        assert!(tap(&mut tracker, 50.0, 50.0, 100.0, 50.0 + DOUBLE_TAP_MS - 1.0).is_empty());
+        assert_eq!(tap(&mut tracker, 50.0, 50.0, 500.0, 550.0).len(), 1);
+    }
+}
@@ -0,0 +1,83 @@
+// Grid geometry in world pixels. Map dimensions come from the server at runtime.
+
+use serde::Serialize;
+
+// Map size until the server announces one
+pub const DEFAULT_GRID_SIZE: u32 = 64;
+// Largest side the client accepts from the server
+pub const MAX_GRID_SIZE: u32 = 1024;
+pub const CELL_SIZE: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MapSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for MapSize {
+    fn default() -> Self {
+        Self { width: DEFAULT_GRID_SIZE, height: DEFAULT_GRID_SIZE }
+    }
+}
+
+impl MapSize {
+    // None for empty or oversized maps
+    pub fn new(width: u32, height: u32) -> Option<Self> {
+        let valid = |side: u32| (1..=MAX_GRID_SIZE).contains(&side);
+        (valid(width) && valid(height)).then_some(Self { width, height })
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height
+    }
+
+    // World extent in pixels at zoom 1
+    pub fn world_width(&self) -> f64 {
+        (self.width * CELL_SIZE) as f64
+    }
+
+    pub fn world_height(&self) -> f64 {
+        (self.height * CELL_SIZE) as f64
+    }
+}
+
+// Top-left corner of a cell in world pixels
+pub fn cell_origin(x: u32, y: u32) -> (f64, f64) {
+    ((x * CELL_SIZE) as f64, (y * CELL_SIZE) as f64)
+}
+
+// Centre of a cell in world pixels
+pub fn cell_center(x: u32, y: u32) -> (f64, f64) {
+    let (origin_x, origin_y) = cell_origin(x, y);
+    (origin_x + CELL_SIZE as f64 / 2.0, origin_y + CELL_SIZE as f64 / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_size_rejects_empty_and_oversized() {
+        assert_eq!(MapSize::new(64, 32), Some(MapSize { width: 64, height: 32 }));
+        assert_eq!(MapSize::new(0, 32), None);
+        assert_eq!(MapSize::new(32, MAX_GRID_SIZE + 1), None);
+        assert!(MapSize::new(MAX_GRID_SIZE, MAX_GRID_SIZE).is_some());
+    }
+
+    #[test]
+    fn contains_excludes_the_far_edges() {
+        let map = MapSize::new(10, 5).unwrap();
+        assert!(map.contains(0, 0));
+        assert!(map.contains(9, 4));
+        assert!(!map.contains(10, 4));
+        assert!(!map.contains(9, 5));
+    }
+
+    #[test]
+    fn cell_geometry_in_world_pixels() {
+        let map = MapSize::new(10, 5).unwrap();
+        assert_eq!((map.world_width(), map.world_height()), (160.0, 80.0));
+        assert_eq!(cell_origin(2, 3), (32.0, 48.0));
+        assert_eq!(cell_center(2, 3), (40.0, 56.0));
+    }
+}
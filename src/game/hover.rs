@@ -0,0 +1,184 @@
+// What the pointer is over on the map: the route our unit would take there
+// and, after a short dwell, what the cell holds.
+
+use super::pathfinding;
+use super::state::GameState;
+use super::status::MAX_HEALTH;
+
+// How long the pointer rests on a cell before its tooltip shows
+pub const TOOLTIP_DELAY_MS: f64 = 400.0;
+
+// Hovered cell, our cell and state revision the current path was planned for
+type PlanInputs = (Option<(u32, u32)>, Option<(u32, u32)>, u64);
+
+#[derive(Debug, Default)]
+pub struct Hover {
+    pub cell: Option<(u32, u32)>,
+    // When the pointer arrived on `cell`
+    since: f64,
+    // Where our unit starts from; None when we have no unit on the map
+    pub from: Option<(u32, u32)>,
+    // Planned route to `cell`, None when it can't be reached
+    pub path: Option<Vec<(u32, u32)>>,
+    planned_for: Option<PlanInputs>,
+}
+
+impl Hover {
+    // Moves the pointer; staying within the same cell keeps the dwell going
+    pub fn point_at(&mut self, cell: Option<(u32, u32)>, now: f64) {
+        if cell != self.cell {
+            self.cell = cell;
+            self.since = now;
+        }
+    }
+
+    // Replans when the hovered cell, our position or the state changed.
+    // Returns true when there is something new to draw.
+    pub fn refresh(&mut self, from: Option<(u32, u32)>, state: &GameState) -> bool {
+        let inputs = (self.cell, from, state.revision);
+        if self.planned_for == Some(inputs) {
+            return false;
+        }
+        self.planned_for = Some(inputs);
+        self.from = from;
+        self.path = match (self.cell, from) {
+            (Some(to), Some(from)) => pathfinding::plan(state, from, to),
+            _ => None,
+        };
+        true
+    }
+
+    // Whether the pointer is resting on a cell whose tooltip isn't shown yet
+    pub fn is_dwelling(&self, now: f64) -> bool {
+        self.cell.is_some() && now - self.since < TOOLTIP_DELAY_MS
+    }
+
+    // Tooltip lines for the hovered cell once the pointer has rested on it:
+    // coordinates and terrain, then every unit standing there
+    pub fn tooltip(&self, state: &GameState, now: f64) -> Option<Vec<String>> {
+        let (x, y) = self.cell?;
+        if now - self.since < TOOLTIP_DELAY_MS {
+            return None;
+        }
+        let terrain = state.terrain.get(x, y)?;
+
+        let mut lines = vec![format!("({}, {}) {}", x, y, terrain.label())];
+        let mut players: Vec<_> = state.players.values()
+            .filter(|player| player.x == x && player.y == y)
+            .collect();
+        players.sort_by(|a, b| a.username.cmp(&b.username));
+        for player in players {
+            lines.push(format!("{}  HP {}/{}  Res {}", player.username, player.health, MAX_HEALTH, player.resources));
+        }
+        Some(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::protocol::{Player, TerrainEncoding, WebSocketMessage};
+
+    // 6x3 map with a rock at (2, 1) and an iron vein at (5, 2); ann at (0, 0)
+    fn state() -> GameState {
+        let mut state = GameState::new("ann", "arena");
+        state.apply(&WebSocketMessage::Terrain {
+            width: 6,
+            height: 3,
+            encoding: TerrainEncoding::Rle,
+            data: "8p,r,8p,i".to_string(),
+        });
+        for (username, x, y, health) in [("ann", 0, 0, 100), ("zed", 5, 2, 40), ("bob", 5, 2, 75)] {
+            let player = Player { username: username.into(), x, y, room: "arena".into(), health, resources: 2 };
+            state.players.insert(username.into(), player);
+        }
+        state
+    }
+
+    #[test]
+    fn dwell_runs_until_the_tooltip_is_due() {
+        let mut hover = Hover::default();
+        assert!(!hover.is_dwelling(0.0));
+
+        hover.point_at(Some((1, 1)), 1_000.0);
+        assert!(hover.is_dwelling(1_000.0 + TOOLTIP_DELAY_MS - 1.0));
+        assert!(!hover.is_dwelling(1_000.0 + TOOLTIP_DELAY_MS));
+
+        // Staying on the cell keeps the original arrival time
+        hover.point_at(Some((1, 1)), 1_200.0);
+        assert!(!hover.is_dwelling(1_000.0 + TOOLTIP_DELAY_MS));
+        // A new cell starts over
+        hover.point_at(Some((2, 1)), 1_500.0);
+        assert!(hover.is_dwelling(1_600.0));
+
+        hover.point_at(None, 2_000.0);
+        assert!(!hover.is_dwelling(2_000.0));
+    }
+
+    #[test]
+    fn tooltip_waits_for_the_dwell() {
+        let (state, mut hover) = (state(), Hover::default());
+        hover.point_at(Some((1, 0)), 0.0);
+        assert_eq!(hover.tooltip(&state, TOOLTIP_DELAY_MS - 1.0), None);
+        assert_eq!(hover.tooltip(&state, TOOLTIP_DELAY_MS), Some(vec!["(1, 0) Plains".to_string()]));
+    }
+
+    #[test]
+    fn tooltip_lists_units_on_the_cell_by_name() {
+        let (state, mut hover) = (state(), Hover::default());
+        hover.point_at(Some((5, 2)), 0.0);
+        assert_eq!(hover.tooltip(&state, TOOLTIP_DELAY_MS).unwrap(), [
+            "(5, 2) Iron vein",
+            "bob  HP 75/100  Res 2",
+            "zed  HP 40/100  Res 2",
+        ]);
+    }
+
+    #[test]
+    fn tooltip_uses_the_right_axis_on_non_square_maps() {
+        let (state, mut hover) = (state(), Hover::default());
+        hover.point_at(Some((2, 1)), 0.0);
+        assert_eq!(hover.tooltip(&state, TOOLTIP_DELAY_MS).unwrap()[0], "(2, 1) Rock");
+        // Inside a 6x6 map, but not this 6x3 one
+        hover.point_at(Some((1, 5)), 0.0);
+        assert_eq!(hover.tooltip(&state, TOOLTIP_DELAY_MS), None);
+        hover.point_at(None, 0.0);
+        assert_eq!(hover.tooltip(&state, TOOLTIP_DELAY_MS), None);
+    }
+
+    #[test]
+    fn refresh_plans_only_when_something_changed() {
+        let mut state = state();
+        let mut hover = Hover::default();
+        hover.point_at(Some((3, 1)), 0.0);
+
+        assert!(hover.refresh(Some((0, 0)), &state));
+        assert_eq!(hover.path.as_ref().map(Vec::len), Some(4));
+        assert!(!hover.refresh(Some((0, 0)), &state));
+
+        // Our unit moved
+        assert!(hover.refresh(Some((0, 1)), &state));
+        assert_eq!(hover.from, Some((0, 1)));
+        // The state changed
+        state.revision += 1;
+        assert!(hover.refresh(Some((0, 1)), &state));
+    }
+
+    #[test]
+    fn unreachable_or_unit_less_hovers_have_no_path() {
+        let state = state();
+        let mut hover = Hover::default();
+
+        hover.point_at(Some((2, 1)), 0.0);
+        hover.refresh(Some((0, 0)), &state);
+        assert_eq!(hover.path, None);
+
+        hover.point_at(Some((5, 2)), 0.0);
+        hover.refresh(Some((0, 0)), &state);
+        assert_eq!(hover.path, None);
+
+        hover.point_at(Some((4, 0)), 0.0);
+        hover.refresh(None, &state);
+        assert_eq!((hover.from, hover.path.clone()), (None, None));
+    }
+}
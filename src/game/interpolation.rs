@@ -0,0 +1,217 @@
+// Snapshot interpolation for player movement. Positions are rendered a short
+// delay behind the newest server update, blending between the two snapshots
+// around that moment, so 10Hz updates still move smoothly at 60fps.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::protocol::Player;
+
+pub const DEFAULT_INTERPOLATION_DELAY_MS: f64 = 100.0;
+pub const MAX_INTERPOLATION_DELAY_MS: f64 = 1000.0;
+
+// Older snapshots are never needed once the render time has passed them
+const SNAPSHOTS_PER_PLAYER: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Snapshot {
+    time: f64,
+    x: f64,
+    y: f64,
+}
+
+#[derive(Debug, Default)]
+struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    fn latest(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    fn push(&mut self, snapshot: Snapshot, delay_ms: f64) {
+        if let Some(&last) = self.latest() {
+            // After standing still, start the step from where the player was
+            // one delay ago instead of from the long-past previous update
+            if last.time < snapshot.time - delay_ms {
+                self.snapshots.push_back(Snapshot { time: snapshot.time - delay_ms, ..last });
+            }
+        }
+        self.snapshots.push_back(snapshot);
+        while self.snapshots.len() > SNAPSHOTS_PER_PLAYER {
+            self.snapshots.pop_front();
+        }
+    }
+
+    // Position at render_time, clamped to the oldest and newest snapshots
+    fn sample(&self, render_time: f64) -> Option<(f64, f64)> {
+        let first = self.snapshots.front()?;
+        if render_time <= first.time {
+            return Some((first.x, first.y));
+        }
+
+        let after = self.snapshots.iter().position(|snapshot| snapshot.time > render_time);
+        match after {
+            Some(i) => {
+                let (from, to) = (self.snapshots[i - 1], self.snapshots[i]);
+                let t = (render_time - from.time) / (to.time - from.time);
+                Some((from.x + (to.x - from.x) * t, from.y + (to.y - from.y) * t))
+            }
+            None => self.latest().map(|last| (last.x, last.y)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Interpolator {
+    delay_ms: f64,
+    buffers: HashMap<String, SnapshotBuffer>,
+}
+
+impl Default for Interpolator {
+    fn default() -> Self {
+        Self::new(DEFAULT_INTERPOLATION_DELAY_MS)
+    }
+}
+
+impl Interpolator {
+    pub fn new(delay_ms: f64) -> Self {
+        Self {
+            delay_ms: delay_ms.clamp(0.0, MAX_INTERPOLATION_DELAY_MS),
+            buffers: HashMap::new(),
+        }
+    }
+
+    pub fn delay_ms(&self) -> f64 {
+        self.delay_ms
+    }
+
+    // Zero renders the newest update immediately, i.e. no smoothing
+    pub fn set_delay(&mut self, delay_ms: f64) {
+        if !delay_ms.is_finite() {
+            return;
+        }
+        self.delay_ms = delay_ms.clamp(0.0, MAX_INTERPOLATION_DELAY_MS);
+    }
+
+    // Records a snapshot for every player that moved, starts buffers for new
+    // players and forgets players that are gone
+    pub fn sync(&mut self, players: &HashMap<String, Player>, now: f64) {
+        self.buffers.retain(|username, _| players.contains_key(username));
+
+        for player in players.values() {
+            let snapshot = Snapshot { time: now, x: player.x as f64, y: player.y as f64 };
+            let buffer = self.buffers.entry(player.username.clone()).or_default();
+            let moved = buffer.latest().is_none_or(|last| (last.x, last.y) != (snapshot.x, snapshot.y));
+            if moved {
+                buffer.push(snapshot, self.delay_ms);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.buffers.clear();
+    }
+
+    // Interpolated position in (fractional) cells
+    pub fn position(&self, username: &str, now: f64) -> Option<(f64, f64)> {
+        self.buffers.get(username)?.sample(now - self.delay_ms)
+    }
+
+    // True while any player is still between snapshots
+    pub fn is_animating(&self, now: f64) -> bool {
+        let render_time = now - self.delay_ms;
+        self.buffers.values().any(|buffer| {
+            buffer.snapshots.len() > 1 && buffer.latest().is_some_and(|last| last.time > render_time)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Just bob, standing at (x, y)
+    fn at(x: u32, y: u32) -> HashMap<String, Player> {
+        let bob = Player { username: "bob".to_string(), x, y, room: "arena".to_string(), health: 80, resources: 3 };
+        HashMap::from([(bob.username.clone(), bob)])
+    }
+
+    fn close(actual: Option<(f64, f64)>, expected: (f64, f64)) -> bool {
+        actual.is_some_and(|(x, y)| (x - expected.0).abs() < 1e-9 && (y - expected.1).abs() < 1e-9)
+    }
+
+    #[test]
+    fn blends_between_the_snapshots_around_the_render_time() {
+        let mut interpolator = Interpolator::new(100.0);
+        interpolator.sync(&at(0, 0), 0.0);
+        interpolator.sync(&at(10, 4), 1_000.0);
+
+        // Render time 950 is halfway through the step, which starts one delay before the update
+        assert!(close(interpolator.position("bob", 1_050.0), (5.0, 2.0)));
+        assert!(close(interpolator.position("bob", 1_000.0), (0.0, 0.0)));
+        assert!(interpolator.is_animating(1_050.0));
+    }
+
+    #[test]
+    fn holds_the_newest_snapshot_past_its_time() {
+        let mut interpolator = Interpolator::new(100.0);
+        interpolator.sync(&at(0, 0), 0.0);
+        interpolator.sync(&at(10, 4), 1_000.0);
+
+        assert!(close(interpolator.position("bob", 1_100.0), (10.0, 4.0)));
+        assert!(close(interpolator.position("bob", 5_000.0), (10.0, 4.0)));
+        assert!(!interpolator.is_animating(1_100.0));
+    }
+
+    #[test]
+    fn renders_the_oldest_snapshot_before_it() {
+        let mut interpolator = Interpolator::new(100.0);
+        interpolator.sync(&at(3, 3), 1_000.0);
+        assert!(close(interpolator.position("bob", 0.0), (3.0, 3.0)));
+        assert_eq!(interpolator.position("nobody", 0.0), None);
+    }
+
+    #[test]
+    fn zero_delay_snaps_to_the_newest_update() {
+        let mut interpolator = Interpolator::new(100.0);
+        interpolator.set_delay(0.0);
+        interpolator.sync(&at(0, 0), 0.0);
+        interpolator.sync(&at(5, 0), 100.0);
+
+        assert!(close(interpolator.position("bob", 100.0), (5.0, 0.0)));
+        assert!(!interpolator.is_animating(100.0));
+    }
+
+    #[test]
+    fn delay_is_clamped_and_ignores_garbage() {
+        let mut interpolator = Interpolator::default();
+        interpolator.set_delay(-5.0);
+        assert_eq!(interpolator.delay_ms(), 0.0);
+        interpolator.set_delay(MAX_INTERPOLATION_DELAY_MS * 2.0);
+        assert_eq!(interpolator.delay_ms(), MAX_INTERPOLATION_DELAY_MS);
+        interpolator.set_delay(f64::NAN);
+        assert_eq!(interpolator.delay_ms(), MAX_INTERPOLATION_DELAY_MS);
+    }
+
+    #[test]
+    fn buffers_keep_only_recent_snapshots() {
+        let mut interpolator = Interpolator::new(100.0);
+        for step in 0..100 {
+            interpolator.sync(&at(step, 0), step as f64 * 50.0);
+        }
+        assert_eq!(interpolator.buffers["bob"].snapshots.len(), SNAPSHOTS_PER_PLAYER);
+        assert!(close(interpolator.position("bob", 99.0 * 50.0 + 100.0), (99.0, 0.0)));
+    }
+
+    #[test]
+    fn standing_still_adds_no_snapshots_and_gone_players_are_forgotten() {
+        let mut interpolator = Interpolator::new(100.0);
+        interpolator.sync(&at(1, 1), 0.0);
+        interpolator.sync(&at(1, 1), 500.0);
+        assert_eq!(interpolator.buffers["bob"].snapshots.len(), 1);
+
+        interpolator.sync(&HashMap::new(), 600.0);
+        assert_eq!(interpolator.position("bob", 600.0), None);
+    }
+}
@@ -0,0 +1,190 @@
+// Keyboard bindings. Keys are `KeyboardEvent.code` values ("KeyW", "ArrowUp"),
+// so WASD stays in the same place on non-QWERTY layouts.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    CenterCamera,
+    FocusChat,
+    // The one binding that still works while typing
+    LeaveChat,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::CenterCamera,
+        Action::FocusChat,
+        Action::LeaveChat,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
+
+    // One-cell step in grid coordinates for movement actions
+    pub fn step(&self) -> Option<(i32, i32)> {
+        match self {
+            Action::MoveUp => Some((0, -1)),
+            Action::MoveDown => Some((0, 1)),
+            Action::MoveLeft => Some((-1, 0)),
+            Action::MoveRight => Some((1, 0)),
+            _ => None,
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::MoveUp => &["KeyW", "ArrowUp"],
+            Action::MoveDown => &["KeyS", "ArrowDown"],
+            Action::MoveLeft => &["KeyA", "ArrowLeft"],
+            Action::MoveRight => &["KeyD", "ArrowRight"],
+            Action::CenterCamera => &["KeyC", "Home"],
+            Action::FocusChat => &["Enter", "KeyT"],
+            Action::LeaveChat => &["Escape"],
+        }
+    }
+}
+
+// Serializes as `{ "move_up": ["KeyW", "ArrowUp"], ... }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<String>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL.iter()
+            .map(|action| (*action, action.default_keys().iter().map(|key| key.to_string()).collect()))
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    // Saved keymaps may predate newer actions; those get their defaults
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let mut keymap: Keymap = serde_json::from_str(json).map_err(|e| format!("Invalid keymap: {}", e))?;
+        for action in Action::ALL {
+            if !keymap.bindings.contains_key(&action) {
+                for key in action.default_keys() {
+                    if keymap.action_for(key).is_none() {
+                        keymap.bindings.entry(action).or_default().push(key.to_string());
+                    }
+                }
+            }
+        }
+        Ok(keymap)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn action_for(&self, key: &str) -> Option<Action> {
+        self.bindings.iter()
+            .find(|(_, keys)| keys.iter().any(|bound| bound == key))
+            .map(|(action, _)| *action)
+    }
+
+    // Adds a key to an action, taking it away from whatever it was bound to
+    pub fn bind(&mut self, action: Action, key: &str) {
+        self.unbind(key);
+        self.bindings.entry(action).or_default().push(key.to_string());
+    }
+
+    pub fn unbind(&mut self, key: &str) {
+        for keys in self.bindings.values_mut() {
+            keys.retain(|bound| bound != key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_cover_every_action() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action_for("KeyW"), Some(Action::MoveUp));
+        assert_eq!(keymap.action_for("ArrowRight"), Some(Action::MoveRight));
+        assert_eq!(keymap.action_for("Escape"), Some(Action::LeaveChat));
+        assert_eq!(keymap.action_for("KeyQ"), None);
+    }
+
+    #[test]
+    fn saved_keymap_round_trips() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::MoveUp, "KeyI");
+        assert_eq!(Keymap::from_json(&keymap.to_json()), Ok(keymap));
+    }
+
+    #[test]
+    fn partial_keymap_fills_missing_actions_with_defaults() {
+        let keymap = Keymap::from_json(r#"{ "move_up": ["KeyI"], "focus_chat": ["KeyT"] }"#).unwrap();
+        assert_eq!(keymap.action_for("KeyI"), Some(Action::MoveUp));
+        // Saved actions keep exactly their saved keys
+        assert_eq!(keymap.action_for("KeyW"), None);
+        assert_eq!(keymap.action_for("Enter"), None);
+        // Missing ones get their defaults
+        assert_eq!(keymap.action_for("KeyS"), Some(Action::MoveDown));
+        assert_eq!(keymap.action_for("Escape"), Some(Action::LeaveChat));
+    }
+
+    #[test]
+    fn missing_actions_do_not_steal_keys_already_in_use() {
+        let keymap = Keymap::from_json(r#"{ "move_up": ["KeyS"] }"#).unwrap();
+        assert_eq!(keymap.action_for("KeyS"), Some(Action::MoveUp));
+        assert_eq!(keymap.action_for("ArrowDown"), Some(Action::MoveDown));
+    }
+
+    #[test]
+    fn corrupt_keymap_is_rejected_so_the_defaults_apply() {
+        for json in ["", "{", "null", "[]", r#"{ "move_up": "KeyW" }"#, r#"{ "jump": ["Space"] }"#] {
+            let loaded = Keymap::from_json(json);
+            assert!(loaded.is_err(), "{:?} was accepted", json);
+            assert_eq!(loaded.unwrap_or_default(), Keymap::default());
+        }
+    }
+
+    #[test]
+    fn rebinding_a_key_removes_its_old_binding() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::FocusChat, "KeyW");
+        assert_eq!(keymap.action_for("KeyW"), Some(Action::FocusChat));
+        // Move up keeps its other key
+        assert_eq!(keymap.action_for("ArrowUp"), Some(Action::MoveUp));
+
+        keymap.bind(Action::FocusChat, "KeyW");
+        let json: serde_json::Value = serde_json::from_str(&keymap.to_json()).unwrap();
+        assert_eq!(json["focus_chat"], serde_json::json!(["Enter", "KeyT", "KeyW"]));
+        assert_eq!(json["move_up"], serde_json::json!(["ArrowUp"]));
+    }
+
+    #[test]
+    fn unbinding_leaves_the_key_free() {
+        let mut keymap = Keymap::default();
+        keymap.unbind("KeyC");
+        assert_eq!(keymap.action_for("KeyC"), None);
+        assert_eq!(keymap.action_for("Home"), Some(Action::CenterCamera));
+    }
+
+    #[test]
+    fn action_names_are_snake_case() {
+        assert_eq!(Action::from_name("center_camera"), Some(Action::CenterCamera));
+        assert_eq!(Action::from_name("CenterCamera"), None);
+    }
+}
@@ -0,0 +1,239 @@
+// Round-trip latency and server clock estimation from ping/pong exchanges.
+// Times are plain milliseconds supplied by the caller so this stays platform-independent.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+
+const LATENCY_WINDOW: usize = 32;
+const PING_TIMEOUT_MS: f64 = 5000.0;
+const CLOCK_SYNC_WINDOW: usize = 8;
+
+// Rolling round-trip statistics exposed to JS
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyStats {
+    samples: usize,
+    pub last_ms: Option<f64>,
+    pub min_ms: Option<f64>,
+    pub avg_ms: Option<f64>,
+    p95_ms: Option<f64>,
+    pub jitter_ms: Option<f64>,
+    pub packet_loss: f64,
+}
+
+// Tracks in-flight pings by sequence number over a rolling window
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+    next_seq: u32,
+    in_flight: VecDeque<(u32, f64)>,
+    rtt_samples: VecDeque<f64>,
+    outcomes: VecDeque<bool>,
+}
+
+impl LatencyTracker {
+    pub fn start_ping(&mut self, now_ms: f64) -> u32 {
+        self.expire(now_ms);
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.in_flight.push_back((seq, now_ms));
+        seq
+    }
+
+    pub fn record_pong(&mut self, seq: u32, now_ms: f64) -> Option<f64> {
+        let index = self.in_flight.iter().position(|(in_flight_seq, _)| *in_flight_seq == seq)?;
+        let (_, sent_at) = self.in_flight.remove(index)?;
+        let rtt = (now_ms - sent_at).max(0.0);
+
+        Self::push_bounded(&mut self.rtt_samples, rtt);
+        Self::push_bounded(&mut self.outcomes, true);
+        Some(rtt)
+    }
+
+    // Pings that never came back within the timeout count as lost
+    pub fn expire(&mut self, now_ms: f64) {
+        while let Some(&(_, sent_at)) = self.in_flight.front() {
+            if now_ms - sent_at < PING_TIMEOUT_MS {
+                break;
+            }
+            self.in_flight.pop_front();
+            Self::push_bounded(&mut self.outcomes, false);
+        }
+    }
+
+    pub fn stats(&self) -> LatencyStats {
+        let mut stats = LatencyStats {
+            samples: self.rtt_samples.len(),
+            last_ms: self.rtt_samples.back().copied(),
+            ..LatencyStats::default()
+        };
+
+        if !self.outcomes.is_empty() {
+            let lost = self.outcomes.iter().filter(|received| !**received).count();
+            stats.packet_loss = lost as f64 / self.outcomes.len() as f64;
+        }
+
+        if self.rtt_samples.is_empty() {
+            return stats;
+        }
+
+        let mut sorted: Vec<f64> = self.rtt_samples.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let p95_index = ((sorted.len() as f64 * 0.95).ceil() as usize).clamp(1, sorted.len()) - 1;
+
+        stats.min_ms = sorted.first().copied();
+        stats.avg_ms = Some(sorted.iter().sum::<f64>() / sorted.len() as f64);
+        stats.p95_ms = Some(sorted[p95_index]);
+
+        // Mean absolute difference between consecutive round trips
+        if self.rtt_samples.len() > 1 {
+            let deltas: f64 = self.rtt_samples.iter()
+                .zip(self.rtt_samples.iter().skip(1))
+                .map(|(a, b)| (b - a).abs())
+                .sum();
+            stats.jitter_ms = Some(deltas / (self.rtt_samples.len() - 1) as f64);
+        }
+
+        stats
+    }
+
+    fn push_bounded<T>(window: &mut VecDeque<T>, value: T) {
+        if window.len() == LATENCY_WINDOW {
+            window.pop_front();
+        }
+        window.push_back(value);
+    }
+}
+
+// NTP-style server clock estimate built from ping round trips. The offset is
+// taken from the fastest recent round trip, where queueing delay (and with it
+// the asymmetry error) is smallest.
+#[derive(Debug, Default)]
+pub struct ClockSync {
+    samples: VecDeque<(f64, f64)>,
+    pub offset_ms: f64,
+}
+
+impl ClockSync {
+    pub fn add_sample(&mut self, client_sent: f64, server_time: f64, client_received: f64) {
+        let rtt = (client_received - client_sent).max(0.0);
+        let offset = server_time - (client_sent + client_received) / 2.0;
+
+        if self.samples.len() == CLOCK_SYNC_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((rtt, offset));
+
+        if let Some(&(_, best_offset)) = self.samples.iter().min_by(|a, b| a.0.total_cmp(&b.0)) {
+            self.offset_ms = best_offset;
+        }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        !self.samples.is_empty()
+    }
+
+    pub fn server_now(&self, client_now: f64) -> f64 {
+        client_now + self.offset_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker_with(rtts: &[f64]) -> LatencyTracker {
+        let mut tracker = LatencyTracker::default();
+        let mut now = 0.0;
+        for rtt in rtts {
+            let seq = tracker.start_ping(now);
+            tracker.record_pong(seq, now + rtt);
+            now += 1_000.0;
+        }
+        tracker
+    }
+
+    #[test]
+    fn stats_summarise_the_round_trips() {
+        let rtts: Vec<f64> = (1..=20).map(|n| n as f64).collect();
+        let stats = tracker_with(&rtts).stats();
+        assert_eq!(stats.samples, 20);
+        assert_eq!(stats.last_ms, Some(20.0));
+        assert_eq!(stats.min_ms, Some(1.0));
+        assert_eq!(stats.avg_ms, Some(10.5));
+        assert_eq!(stats.p95_ms, Some(19.0));
+        assert_eq!(stats.packet_loss, 0.0);
+    }
+
+    #[test]
+    fn p95_of_a_single_sample_is_that_sample() {
+        assert_eq!(tracker_with(&[42.0]).stats().p95_ms, Some(42.0));
+        assert_eq!(tracker_with(&[]).stats().p95_ms, None);
+    }
+
+    #[test]
+    fn jitter_is_the_mean_change_between_round_trips() {
+        assert_eq!(tracker_with(&[10.0, 20.0, 10.0, 10.0]).stats().jitter_ms, Some(20.0 / 3.0));
+        assert_eq!(tracker_with(&[10.0]).stats().jitter_ms, None);
+    }
+
+    #[test]
+    fn unanswered_pings_count_as_lost_after_the_timeout() {
+        let mut tracker = LatencyTracker::default();
+        let answered = tracker.start_ping(0.0);
+        tracker.start_ping(0.0);
+        tracker.record_pong(answered, 50.0);
+
+        tracker.expire(PING_TIMEOUT_MS - 1.0);
+        assert_eq!(tracker.stats().packet_loss, 0.0);
+        tracker.expire(PING_TIMEOUT_MS);
+        assert_eq!(tracker.stats().packet_loss, 0.5);
+    }
+
+    #[test]
+    fn unknown_or_repeated_pongs_are_ignored() {
+        let mut tracker = LatencyTracker::default();
+        let seq = tracker.start_ping(0.0);
+        assert_eq!(tracker.record_pong(seq + 1, 10.0), None);
+        assert_eq!(tracker.record_pong(seq, 10.0), Some(10.0));
+        assert_eq!(tracker.record_pong(seq, 20.0), None);
+        assert_eq!(tracker.stats().samples, 1);
+    }
+
+    #[test]
+    fn window_keeps_only_recent_round_trips() {
+        let mut rtts = vec![1.0; LATENCY_WINDOW];
+        rtts.push(500.0);
+        let stats = tracker_with(&rtts).stats();
+        assert_eq!(stats.samples, LATENCY_WINDOW);
+        assert_eq!(stats.last_ms, Some(500.0));
+    }
+
+    #[test]
+    fn clock_offset_comes_from_the_fastest_round_trip() {
+        let mut clock = ClockSync::default();
+        assert!(!clock.is_synced());
+
+        // 100ms round trip: server clock ~950ms ahead
+        clock.add_sample(0.0, 1_000.0, 100.0);
+        assert_eq!(clock.offset_ms, 950.0);
+        // 20ms round trip is more trustworthy even though it disagrees
+        clock.add_sample(200.0, 1_300.0, 220.0);
+        assert_eq!(clock.offset_ms, 1_090.0);
+        // A slower sample does not replace it
+        clock.add_sample(400.0, 2_000.0, 700.0);
+        assert_eq!(clock.offset_ms, 1_090.0);
+
+        assert!(clock.is_synced());
+        assert_eq!(clock.server_now(1_000.0), 2_090.0);
+    }
+
+    #[test]
+    fn clock_forgets_samples_outside_its_window() {
+        let mut clock = ClockSync::default();
+        clock.add_sample(0.0, 1_010.0, 20.0);
+        for n in 0..CLOCK_SYNC_WINDOW {
+            let sent = 1_000.0 * (n + 1) as f64;
+            clock.add_sample(sent, sent + 550.0, sent + 100.0);
+        }
+        assert_eq!(clock.offset_ms, 500.0);
+    }
+}
@@ -0,0 +1,169 @@
+// Player entity lifecycle on the client: when each player was last heard of,
+// silent players that should be expired, and departed players fading out.
+
+use std::collections::HashMap;
+
+use super::protocol::Player;
+
+pub const DESPAWN_FADE_MS: f64 = 600.0;
+// Expiry is off by default: the server only sends player_update when
+// something changes, so a player standing still would look silent. Enable it
+// only against a server that sends keepalives.
+pub const DEFAULT_STALE_PLAYER_MS: f64 = 0.0;
+
+// Expiry only needs to be roughly on time
+const EXPIRY_CHECK_INTERVAL_MS: f64 = 1_000.0;
+
+// A player that left, drawn fading out where it was last seen
+#[derive(Debug, Clone)]
+pub struct Departed {
+    pub player: Player,
+    // Drawn position in (fractional) cells
+    pub x: f64,
+    pub y: f64,
+    pub left_at: f64,
+}
+
+impl Departed {
+    // 1.0 when it just left, 0.0 once fully faded
+    pub fn opacity(&self, now: f64) -> f64 {
+        (1.0 - (now - self.left_at) / DESPAWN_FADE_MS).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Debug)]
+pub struct Lifecycle {
+    // Zero disables expiry
+    stale_after_ms: f64,
+    last_seen: HashMap<String, f64>,
+    departed: Vec<Departed>,
+    next_check: f64,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            stale_after_ms: DEFAULT_STALE_PLAYER_MS,
+            last_seen: HashMap::new(),
+            departed: Vec::new(),
+            next_check: 0.0,
+        }
+    }
+}
+
+impl Lifecycle {
+    pub fn stale_after_ms(&self) -> f64 {
+        self.stale_after_ms
+    }
+
+    pub fn set_stale_after(&mut self, stale_after_ms: f64) {
+        if stale_after_ms.is_finite() {
+            self.stale_after_ms = stale_after_ms.max(0.0);
+        }
+    }
+
+    pub fn seen(&mut self, username: &str, now: f64) {
+        self.last_seen.insert(username.to_string(), now);
+    }
+
+    pub fn depart(&mut self, player: Player, (x, y): (f64, f64), now: f64) {
+        self.last_seen.remove(&player.username);
+        // Rejoining and leaving again restarts the fade instead of drawing two ghosts
+        self.departed.retain(|departed| departed.player.username != player.username);
+        self.departed.push(Departed { player, x, y, left_at: now });
+    }
+
+    // A player that is (back) on the map is no longer fading out
+    pub fn arrive(&mut self, username: &str) {
+        self.departed.retain(|departed| departed.player.username != username);
+    }
+
+    pub fn clear(&mut self) {
+        self.last_seen.clear();
+        self.departed.clear();
+    }
+
+    // Players other than `local` that nothing was heard of for too long.
+    // Also drops finished fades. Cheap to call every frame.
+    pub fn expire(&mut self, players: &HashMap<String, Player>, local: &str, now: f64) -> Vec<String> {
+        self.departed.retain(|departed| departed.opacity(now) > 0.0);
+
+        if now < self.next_check || self.stale_after_ms <= 0.0 {
+            return Vec::new();
+        }
+        self.next_check = now + EXPIRY_CHECK_INTERVAL_MS;
+
+        players.keys()
+            .filter(|username| username.as_str() != local)
+            .filter(|username| {
+                // Players we never timed (e.g. added through the JS API) start counting now
+                let last_seen = *self.last_seen.entry(username.to_string()).or_insert(now);
+                now - last_seen > self.stale_after_ms
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn departed(&self) -> &[Departed] {
+        &self.departed
+    }
+
+    pub fn is_animating(&self, now: f64) -> bool {
+        self.departed.iter().any(|departed| departed.opacity(now) > 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players(names: &[&str]) -> HashMap<String, Player> {
+        names.iter()
+            .map(|name| {
+                let player = Player { username: name.to_string(), x: 0, y: 0, room: String::new(), health: 100, resources: 0 };
+                (name.to_string(), player)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expiry_is_off_by_default() {
+        let mut lifecycle = Lifecycle::default();
+        lifecycle.seen("bob", 0.0);
+        assert!(lifecycle.expire(&players(&["alice", "bob"]), "alice", 1e9).is_empty());
+    }
+
+    #[test]
+    fn silent_players_expire_except_the_local_one() {
+        let mut lifecycle = Lifecycle::default();
+        lifecycle.set_stale_after(1_000.0);
+        lifecycle.seen("alice", 0.0);
+        lifecycle.seen("bob", 0.0);
+        lifecycle.seen("carol", 0.0);
+        lifecycle.seen("carol", 900.0);
+
+        let expired = lifecycle.expire(&players(&["alice", "bob", "carol"]), "alice", 1_500.0);
+        assert_eq!(expired, ["bob"]);
+    }
+
+    #[test]
+    fn departed_players_fade_out_and_are_pruned() {
+        let mut lifecycle = Lifecycle::default();
+        let bob = players(&["bob"]).remove("bob").unwrap();
+        lifecycle.depart(bob, (2.0, 3.0), 0.0);
+        assert_eq!(lifecycle.departed()[0].opacity(DESPAWN_FADE_MS / 2.0), 0.5);
+        assert!(lifecycle.is_animating(DESPAWN_FADE_MS - 1.0));
+
+        lifecycle.expire(&HashMap::new(), "alice", DESPAWN_FADE_MS);
+        assert!(lifecycle.departed().is_empty());
+    }
+
+    #[test]
+    fn arriving_again_cancels_the_fade() {
+        let mut lifecycle = Lifecycle::default();
+        let bob = players(&["bob"]).remove("bob").unwrap();
+        lifecycle.depart(bob, (0.0, 0.0), 0.0);
+        lifecycle.arrive("bob");
+        assert!(lifecycle.departed().is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+// Overview of the whole map in a corner of the canvas: where it sits on the
+// canvas and conversions between minimap pixels and the world.
+
+use super::camera::Camera;
+use super::grid::CELL_SIZE;
+
+pub const MINIMAP_MAX_PX: f64 = 160.0;
+// On small canvases the minimap takes at most this share of the shorter side
+const MINIMAP_MAX_FRACTION: f64 = 0.3;
+const MINIMAP_MARGIN_PX: f64 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Minimap {
+    pub visible: bool,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self { visible: true }
+    }
+}
+
+impl Minimap {
+    // Placement in the bottom-right corner for the current canvas and world,
+    // or None while hidden
+    pub fn layout(&self, camera: &Camera) -> Option<MinimapView> {
+        if !self.visible {
+            return None;
+        }
+        let longest = (camera.viewport_width.min(camera.viewport_height) * MINIMAP_MAX_FRACTION).min(MINIMAP_MAX_PX);
+        let scale = longest / camera.world_width.max(camera.world_height);
+        let (width, height) = (camera.world_width * scale, camera.world_height * scale);
+        Some(MinimapView {
+            rect: Rect {
+                x: camera.viewport_width - MINIMAP_MARGIN_PX - width,
+                y: camera.viewport_height - MINIMAP_MARGIN_PX - height,
+                width,
+                height,
+            },
+            scale,
+        })
+    }
+
+    // Grid cell under a canvas pixel, if that pixel is on the minimap
+    pub fn screen_to_cell(&self, camera: &Camera, screen_x: f64, screen_y: f64) -> Option<(u32, u32)> {
+        let view = self.layout(camera)?;
+        let (world_x, world_y) = view.screen_to_world(screen_x, screen_y)?;
+        let cell = |world: f64, limit: f64| (world.min(limit - 1.0) / CELL_SIZE as f64) as u32;
+        Some((cell(world_x, camera.world_width), cell(world_y, camera.world_height)))
+    }
+
+    // World pixel under a canvas pixel, if that pixel is on the minimap
+    pub fn screen_to_world(&self, camera: &Camera, screen_x: f64, screen_y: f64) -> Option<(f64, f64)> {
+        self.layout(camera)?.screen_to_world(screen_x, screen_y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimapView {
+    // Canvas pixels the minimap covers
+    pub rect: Rect,
+    // Minimap pixels per world pixel
+    pub scale: f64,
+}
+
+impl MinimapView {
+    pub fn screen_to_world(&self, screen_x: f64, screen_y: f64) -> Option<(f64, f64)> {
+        if !self.rect.contains(screen_x, screen_y) {
+            return None;
+        }
+        Some(((screen_x - self.rect.x) / self.scale, (screen_y - self.rect.y) / self.scale))
+    }
+
+    pub fn world_to_screen(&self, world_x: f64, world_y: f64) -> (f64, f64) {
+        (self.rect.x + world_x * self.scale, self.rect.y + world_y * self.scale)
+    }
+
+    // The part of the world the camera shows, in canvas pixels on the minimap
+    pub fn viewport(&self, camera: &Camera) -> Rect {
+        let (left, top) = camera.screen_to_world(0.0, 0.0);
+        let (right, bottom) = camera.screen_to_world(camera.viewport_width, camera.viewport_height);
+        let (left, right) = (left.max(0.0), right.min(camera.world_width));
+        let (top, bottom) = (top.max(0.0), bottom.min(camera.world_height));
+        let (x, y) = self.world_to_screen(left, top);
+        Rect { x, y, width: (right - left) * self.scale, height: (bottom - top) * self.scale }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 128x64 cells (2048x1024 world pixels) fitted into an 800x600 canvas.
+    // The minimap is 160x80 at 1/12.8 scale, 8px in from the bottom-right corner.
+    fn camera() -> Camera {
+        let mut camera = Camera::new(2048.0, 1024.0);
+        camera.set_viewport(800.0, 600.0);
+        camera.fit();
+        camera
+    }
+
+    fn view() -> MinimapView {
+        Minimap::default().layout(&camera()).unwrap()
+    }
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn layout_keeps_the_map_aspect_in_the_corner() {
+        let view = view();
+        assert_eq!(view.rect, Rect { x: 632.0, y: 512.0, width: 160.0, height: 80.0 });
+        assert_eq!(view.scale, 160.0 / 2048.0);
+    }
+
+    #[test]
+    fn layout_shrinks_on_small_canvases() {
+        let mut camera = Camera::new(1024.0, 2048.0);
+        camera.set_viewport(300.0, 200.0);
+        let view = Minimap::default().layout(&camera).unwrap();
+        // 30% of the 200px side, along the map's longer (vertical) side
+        assert_eq!((view.rect.width, view.rect.height), (30.0, 60.0));
+        assert_eq!((view.rect.x, view.rect.y), (262.0, 132.0));
+    }
+
+    #[test]
+    fn hidden_minimap_has_no_layout_and_takes_no_clicks() {
+        let hidden = Minimap { visible: false };
+        assert_eq!(hidden.layout(&camera()), None);
+        assert_eq!(hidden.screen_to_cell(&camera(), 700.0, 550.0), None);
+    }
+
+    #[test]
+    fn world_and_minimap_round_trip() {
+        let view = view();
+        for world in [(0.0, 0.0), (1024.0, 512.0), (2000.0, 1000.0), (37.5, 900.25)] {
+            let (screen_x, screen_y) = view.world_to_screen(world.0, world.1);
+            assert!(close(view.screen_to_world(screen_x, screen_y).unwrap(), world), "{:?}", world);
+        }
+        assert!(close(view.world_to_screen(2048.0, 1024.0), (792.0, 592.0)));
+    }
+
+    #[test]
+    fn clicks_off_the_minimap_are_ignored() {
+        let view = view();
+        assert_eq!(view.screen_to_world(631.9, 550.0), None);
+        assert_eq!(view.screen_to_world(700.0, 511.9), None);
+        assert_eq!(view.screen_to_world(792.0, 550.0), None);
+        assert_eq!(view.screen_to_world(700.0, 592.0), None);
+        assert!(view.screen_to_world(632.0, 512.0).is_some());
+    }
+
+    #[test]
+    fn clicks_map_to_cells_on_non_square_maps() {
+        let (minimap, camera) = (Minimap::default(), camera());
+        assert_eq!(minimap.screen_to_cell(&camera, 632.0, 512.0), Some((0, 0)));
+        assert_eq!(minimap.screen_to_cell(&camera, 712.0, 552.0), Some((64, 32)));
+        // The far edge is still the last cell
+        assert_eq!(minimap.screen_to_cell(&camera, 791.999, 591.999), Some((127, 63)));
+        assert_eq!(minimap.screen_to_cell(&camera, 100.0, 100.0), None);
+    }
+
+    #[test]
+    fn viewport_covers_the_whole_map_when_fitted() {
+        let view = view();
+        assert_eq!(view.viewport(&camera()), view.rect);
+    }
+
+    #[test]
+    fn viewport_follows_zoom_and_pan() {
+        let view = view();
+        let mut camera = camera();
+        camera.zoom = 1.0;
+        camera.center_on(1024.0, 512.0);
+
+        // 800x600 world pixels around the centre
+        let rect = view.viewport(&camera);
+        let expected = Rect { x: 632.0 + 624.0 * view.scale, y: 512.0 + 212.0 * view.scale, width: 62.5, height: 46.875 };
+        assert!(close((rect.x, rect.y), (expected.x, expected.y)));
+        assert!(close((rect.width, rect.height), (expected.width, expected.height)));
+
+        // Panned into the top-left corner
+        camera.center_on(0.0, 0.0);
+        let rect = view.viewport(&camera);
+        assert!(close((rect.x, rect.y), (632.0, 512.0)));
+        assert!(close((rect.width, rect.height), (62.5, 46.875)));
+    }
+}
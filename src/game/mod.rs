@@ -0,0 +1,26 @@
+// Platform-independent game core. Nothing in here touches web_sys or js_sys,
+// so it builds and runs under `cargo test` on native targets; lib.rs is the
+// thin wasm adapter that feeds it socket messages, timestamps and DOM input.
+
+pub mod camera;
+pub mod chat;
+pub mod connection;
+pub mod events;
+pub mod gesture;
+pub mod grid;
+pub mod hover;
+pub mod interpolation;
+pub mod keymap;
+pub mod latency;
+pub mod lifecycle;
+pub mod minimap;
+pub mod mode;
+pub mod motion;
+pub mod outbox;
+pub mod pathfinding;
+pub mod prediction;
+pub mod protocol;
+pub mod sprites;
+pub mod state;
+pub mod status;
+pub mod terrain;
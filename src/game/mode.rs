@@ -0,0 +1,125 @@
+// What the client is doing on the socket, and which server messages matter in each mode.
+
+use serde::Serialize;
+
+use super::protocol::WebSocketMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientMode {
+    // No open socket
+    Disconnected,
+    // Connected for chat, no unit spawned
+    Lobby,
+    // Joined with a unit we control
+    InBattle,
+    // Watching the battle without a unit
+    Spectating,
+}
+
+impl ClientMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClientMode::Disconnected => "disconnected",
+            ClientMode::Lobby => "lobby",
+            ClientMode::InBattle => "in_battle",
+            ClientMode::Spectating => "spectating",
+        }
+    }
+
+    // Whether a server message should be handled in this mode
+    pub fn accepts(&self, message: &WebSocketMessage) -> bool {
+        match self {
+            ClientMode::Disconnected => false,
+            ClientMode::Lobby => matches!(
+                message,
+                WebSocketMessage::ChatMessage(_) | WebSocketMessage::Pong { .. } | WebSocketMessage::Error { .. }
+            ),
+            ClientMode::InBattle | ClientMode::Spectating => true,
+        }
+    }
+
+    // Only a joined player may issue commands for a unit
+    pub fn can_command(&self) -> bool {
+        *self == ClientMode::InBattle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    use crate::game::protocol::{ChatMessage, TerrainEncoding};
+
+    const MODES: [ClientMode; 4] = [ClientMode::Disconnected, ClientMode::Lobby, ClientMode::InBattle, ClientMode::Spectating];
+
+    // Every message with whether Disconnected, Lobby, InBattle and Spectating accept it
+    fn table() -> Vec<(WebSocketMessage, [bool; 4])> {
+        let text = |s: &str| s.to_string();
+        let chat = ChatMessage {
+            id: 1.into(), username: text("ann"), message: text("hi"), timestamp: 0.into(), room: text("lobby"), nonce: None,
+        };
+        vec![
+            (WebSocketMessage::Join { username: text("ann"), room: text("lobby") }, [false, false, true, true]),
+            (WebSocketMessage::Message { username: text("ann"), message: text("hi"), room: text("lobby"), nonce: None }, [false, false, true, true]),
+            (WebSocketMessage::ChatMessage(chat), [false, true, true, true]),
+            (WebSocketMessage::PlayerJoined { username: text("ann"), x: 0, y: 0 }, [false, false, true, true]),
+            (WebSocketMessage::PlayerLeft { username: text("ann") }, [false, false, true, true]),
+            (WebSocketMessage::Error { message: text("nope") }, [false, true, true, true]),
+            (WebSocketMessage::Move { username: text("ann"), x: 0, y: 0, room: text("lobby"), seq: None, path: None }, [false, false, true, true]),
+            (WebSocketMessage::PlayerUpdate { username: text("ann"), x: 0, y: 0, health: 100, resources: 0, seq: None }, [false, false, true, true]),
+            (WebSocketMessage::GameState { players: Vec::new(), width: None, height: None }, [false, false, true, true]),
+            (WebSocketMessage::MapInfo { width: 8, height: 8 }, [false, false, true, true]),
+            (WebSocketMessage::Terrain { width: 1, height: 1, encoding: TerrainEncoding::Rle, data: text("p") }, [false, false, true, true]),
+            (WebSocketMessage::Ping { seq: 0, client_time: 0.0 }, [false, false, true, true]),
+            (WebSocketMessage::Pong { seq: 0, client_time: 0.0, server_time: 0.0 }, [false, true, true, true]),
+        ]
+    }
+
+    // No wildcard, so a new message type fails to compile until the table lists it
+    fn kind(message: &WebSocketMessage) -> &'static str {
+        match message {
+            WebSocketMessage::Join { .. } => "join",
+            WebSocketMessage::Message { .. } => "message",
+            WebSocketMessage::ChatMessage(_) => "chat_message",
+            WebSocketMessage::PlayerJoined { .. } => "player_joined",
+            WebSocketMessage::PlayerLeft { .. } => "player_left",
+            WebSocketMessage::Error { .. } => "error",
+            WebSocketMessage::Move { .. } => "move",
+            WebSocketMessage::PlayerUpdate { .. } => "player_update",
+            WebSocketMessage::GameState { .. } => "game_state",
+            WebSocketMessage::MapInfo { .. } => "map_info",
+            WebSocketMessage::Terrain { .. } => "terrain",
+            WebSocketMessage::Ping { .. } => "ping",
+            WebSocketMessage::Pong { .. } => "pong",
+        }
+    }
+
+    #[test]
+    fn accepts_matches_the_table_for_every_mode_and_message() {
+        let table = table();
+        let kinds: HashSet<&str> = table.iter().map(|(message, _)| kind(message)).collect();
+        assert_eq!(kinds.len(), 13);
+
+        for (message, expected) in &table {
+            for (mode, accepted) in MODES.iter().zip(expected) {
+                assert_eq!(mode.accepts(message), *accepted, "{:?} accepting {}", mode, kind(message));
+            }
+        }
+    }
+
+    #[test]
+    fn only_battle_can_command() {
+        let commanding: Vec<bool> = MODES.iter().map(|mode| mode.can_command()).collect();
+        assert_eq!(commanding, [false, false, true, false]);
+    }
+
+    #[test]
+    fn names_are_snake_case_and_match_serde() {
+        for mode in MODES {
+            assert_eq!(serde_json::to_value(mode).unwrap(), mode.as_str());
+        }
+        assert_eq!(ClientMode::InBattle.as_str(), "in_battle");
+    }
+}
@@ -0,0 +1,68 @@
+// Where each player is drawn: our own unit from local prediction, everyone
+// else interpolated between server snapshots, departed players fading out,
+// and damage/heal numbers floating above units.
+
+use super::interpolation::Interpolator;
+use super::lifecycle::Lifecycle;
+use super::prediction::Predictor;
+use super::protocol::{Player, WebSocketMessage};
+use super::status::StatusEffects;
+
+#[derive(Debug, Default)]
+pub struct Motion {
+    pub interpolation: Interpolator,
+    pub prediction: Predictor,
+    pub lifecycle: Lifecycle,
+    pub status: StatusEffects,
+    local: String,
+}
+
+impl Motion {
+    // Forgets all movement history for a new local player; settings are kept
+    pub fn reset(&mut self, local: &str) {
+        self.interpolation.clear();
+        self.prediction.reset();
+        self.lifecycle.clear();
+        self.status.clear();
+        self.local = local.to_string();
+    }
+
+    // Notes which players a server message shows to be alive
+    pub fn observe(&mut self, message: &WebSocketMessage, now: f64) {
+        match message {
+            WebSocketMessage::PlayerJoined { username, .. } | WebSocketMessage::PlayerUpdate { username, .. } => {
+                self.lifecycle.arrive(username);
+                self.lifecycle.seen(username, now);
+            }
+            WebSocketMessage::GameState { players, .. } => {
+                for player in players {
+                    self.lifecycle.arrive(&player.username);
+                    self.lifecycle.seen(&player.username, now);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Starts the fade-out where the player is currently drawn. Call before the
+    // interpolation buffers forget the player.
+    pub fn depart(&mut self, player: Player, now: f64) {
+        let drawn = self.position(&player.username, now)
+            .unwrap_or((player.x as f64, player.y as f64));
+        if player.username == self.local {
+            self.prediction.reset();
+        }
+        self.lifecycle.depart(player, drawn, now);
+    }
+
+    // Drawn position in (fractional) cells
+    pub fn position(&self, username: &str, now: f64) -> Option<(f64, f64)> {
+        let predicted = if username == self.local { self.prediction.position(now) } else { None };
+        predicted.or_else(|| self.interpolation.position(username, now))
+    }
+
+    pub fn is_animating(&self, now: f64) -> bool {
+        self.interpolation.is_animating(now) || self.prediction.is_animating(now) || self.lifecycle.is_animating(now)
+            || self.status.is_animating(now)
+    }
+}
@@ -0,0 +1,224 @@
+// Bounded queue of outbound intents held while the socket is (re)connecting.
+
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+use super::protocol::WebSocketMessage;
+
+// Queued items beyond this push out the oldest one
+pub const OUTBOX_CAPACITY: usize = 32;
+// Movement intents older than this are pointless to replay after a reconnect
+pub const MOVE_STALE_MS: f64 = 3_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscardReason {
+    // A newer move replaced it
+    Superseded,
+    // Too old to send once the socket came back
+    Stale,
+    // The queue was full
+    Overflow,
+    // The connection was closed for good
+    Disconnected,
+}
+
+#[derive(Debug)]
+pub struct Discarded {
+    pub message: WebSocketMessage,
+    pub reason: DiscardReason,
+}
+
+impl Discarded {
+    pub fn message_type(&self) -> &'static str {
+        match self.message {
+            WebSocketMessage::Move { .. } => "move",
+            WebSocketMessage::Message { .. } => "message",
+            _ => "other",
+        }
+    }
+
+    // Nonce of a discarded chat line, so its pending entry can be failed
+    pub fn nonce(&self) -> Option<&str> {
+        match self.message {
+            WebSocketMessage::Message { ref nonce, .. } => nonce.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct QueuedMessage {
+    message: WebSocketMessage,
+    queued_at: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct Outbox {
+    items: VecDeque<QueuedMessage>,
+}
+
+impl Outbox {
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    // Queues a message and returns whatever it displaced
+    pub fn push(&mut self, message: WebSocketMessage, now_ms: f64) -> Vec<Discarded> {
+        let mut discarded = Vec::new();
+
+        // Only the latest movement intent matters
+        if matches!(message, WebSocketMessage::Move { .. }) {
+            let (moves, rest): (VecDeque<_>, VecDeque<_>) = self.items.drain(..)
+                .partition(|item| matches!(item.message, WebSocketMessage::Move { .. }));
+            self.items = rest;
+            discarded.extend(moves.into_iter().map(|item| Discarded {
+                message: item.message,
+                reason: DiscardReason::Superseded,
+            }));
+        }
+
+        while self.items.len() >= OUTBOX_CAPACITY {
+            let Some(item) = self.items.pop_front() else { break };
+            discarded.push(Discarded { message: item.message, reason: DiscardReason::Overflow });
+        }
+
+        self.items.push_back(QueuedMessage { message, queued_at: now_ms });
+        discarded
+    }
+
+    // Empties the queue in order, splitting out moves that went stale while queued
+    pub fn drain(&mut self, now_ms: f64) -> (Vec<WebSocketMessage>, Vec<Discarded>) {
+        let mut ready = Vec::new();
+        let mut discarded = Vec::new();
+
+        for item in self.items.drain(..) {
+            let is_move = matches!(item.message, WebSocketMessage::Move { .. });
+            if is_move && now_ms - item.queued_at > MOVE_STALE_MS {
+                discarded.push(Discarded { message: item.message, reason: DiscardReason::Stale });
+            } else {
+                ready.push(item.message);
+            }
+        }
+
+        (ready, discarded)
+    }
+
+    pub fn clear(&mut self) -> Vec<Discarded> {
+        self.items.drain(..)
+            .map(|item| Discarded { message: item.message, reason: DiscardReason::Disconnected })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(nonce: &str) -> WebSocketMessage {
+        WebSocketMessage::Message {
+            username: "ann".into(),
+            message: format!("line {}", nonce),
+            room: "lobby".into(),
+            nonce: Some(nonce.into()),
+        }
+    }
+
+    fn step(x: u32) -> WebSocketMessage {
+        WebSocketMessage::Move { username: "ann".into(), x, y: 0, room: "lobby".into(), seq: None, path: None }
+    }
+
+    fn nonces(messages: &[WebSocketMessage]) -> Vec<&str> {
+        messages.iter()
+            .map(|message| match message {
+                WebSocketMessage::Message { nonce, .. } => nonce.as_deref().unwrap(),
+                WebSocketMessage::Move { .. } => "move",
+                _ => "other",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn newer_move_replaces_the_queued_one() {
+        let mut outbox = Outbox::default();
+        assert!(outbox.push(step(1), 0.0).is_empty());
+        outbox.push(chat("a"), 0.0);
+
+        let discarded = outbox.push(step(2), 10.0);
+        assert_eq!(discarded.len(), 1);
+        assert_eq!(discarded[0].reason, DiscardReason::Superseded);
+        assert!(matches!(discarded[0].message, WebSocketMessage::Move { x: 1, .. }));
+
+        let (ready, _) = outbox.drain(10.0);
+        assert_eq!(nonces(&ready), ["a", "move"]);
+        assert!(matches!(ready[1], WebSocketMessage::Move { x: 2, .. }));
+    }
+
+    #[test]
+    fn stale_moves_are_dropped_on_drain() {
+        let mut outbox = Outbox::default();
+        outbox.push(step(1), 0.0);
+        outbox.push(chat("a"), 0.0);
+
+        let (ready, discarded) = outbox.drain(MOVE_STALE_MS + 1.0);
+        assert_eq!(nonces(&ready), ["a"]);
+        assert_eq!(discarded.len(), 1);
+        assert_eq!(discarded[0].reason, DiscardReason::Stale);
+        assert_eq!(discarded[0].message_type(), "move");
+        assert_eq!(outbox.len(), 0);
+    }
+
+    #[test]
+    fn fresh_moves_survive_the_drain() {
+        let mut outbox = Outbox::default();
+        outbox.push(step(1), 0.0);
+        let (ready, discarded) = outbox.drain(MOVE_STALE_MS);
+        assert_eq!(ready.len(), 1);
+        assert!(discarded.is_empty());
+    }
+
+    #[test]
+    fn overflow_evicts_the_oldest_items() {
+        let mut outbox = Outbox::default();
+        for n in 0..OUTBOX_CAPACITY {
+            assert!(outbox.push(chat(&n.to_string()), 0.0).is_empty());
+        }
+
+        let discarded = outbox.push(chat("new"), 0.0);
+        assert_eq!(discarded.len(), 1);
+        assert_eq!(discarded[0].reason, DiscardReason::Overflow);
+        assert_eq!(discarded[0].nonce(), Some("0"));
+        assert_eq!(outbox.len(), OUTBOX_CAPACITY);
+
+        let (ready, _) = outbox.drain(0.0);
+        assert_eq!(nonces(&ready).first(), Some(&"1"));
+        assert_eq!(nonces(&ready).last(), Some(&"new"));
+    }
+
+    #[test]
+    fn drain_keeps_queue_order() {
+        let mut outbox = Outbox::default();
+        outbox.push(chat("a"), 0.0);
+        outbox.push(step(1), 0.0);
+        outbox.push(chat("b"), 0.0);
+        outbox.push(chat("c"), 0.0);
+
+        let (ready, discarded) = outbox.drain(0.0);
+        assert_eq!(nonces(&ready), ["a", "move", "b", "c"]);
+        assert!(discarded.is_empty());
+        assert_eq!(outbox.len(), 0);
+    }
+
+    #[test]
+    fn clear_reports_everything_as_disconnected() {
+        let mut outbox = Outbox::default();
+        outbox.push(chat("a"), 0.0);
+        outbox.push(step(1), 0.0);
+
+        let discarded = outbox.clear();
+        assert_eq!(discarded.iter().map(|item| item.reason).collect::<Vec<_>>(), [DiscardReason::Disconnected; 2]);
+        assert_eq!(discarded[0].nonce(), Some("a"));
+        assert_eq!(outbox.len(), 0);
+    }
+}
@@ -0,0 +1,207 @@
+// A* over the grid: 4-directional steps, avoiding unwalkable terrain and
+// cells other players stand on. Used to preview routes and to send the server
+// the waypoints our unit should follow.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use super::state::GameState;
+use super::terrain::TileMap;
+
+// Give up rather than stall a frame on huge maps; such targets show as unreachable
+pub const MAX_EXPANDED_NODES: usize = 20_000;
+
+const STEPS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+// Cells from `from` (exclusive) to `to` (inclusive), or None when there is no route
+pub fn find_path(
+    terrain: &TileMap,
+    occupied: &HashSet<(u32, u32)>,
+    from: (u32, u32),
+    to: (u32, u32),
+) -> Option<Vec<(u32, u32)>> {
+    if from == to {
+        return Some(Vec::new());
+    }
+    if !terrain.is_walkable(to.0, to.1) || occupied.contains(&to) {
+        return None;
+    }
+
+    let heuristic = |(x, y): (u32, u32)| x.abs_diff(to.0) + y.abs_diff(to.1);
+
+    // (f, g, cell); Reverse makes the max-heap pop the lowest f first. On equal f
+    // the higher g goes first, so on open ground the search runs straight at the
+    // target instead of filling the whole rectangle between the two cells.
+    let mut open = BinaryHeap::new();
+    let mut cost: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut came_from: HashMap<(u32, u32), (u32, u32)> = HashMap::new();
+    open.push(Reverse((heuristic(from), Reverse(0), from)));
+    cost.insert(from, 0);
+
+    let mut expanded = 0;
+    while let Some(Reverse((_, Reverse(g), cell))) = open.pop() {
+        if cell == to {
+            return Some(reconstruct(&came_from, from, to));
+        }
+        // Skip entries superseded by a cheaper route
+        if cost.get(&cell).is_some_and(|best| g > *best) {
+            continue;
+        }
+        expanded += 1;
+        if expanded > MAX_EXPANDED_NODES {
+            return None;
+        }
+
+        for (dx, dy) in STEPS {
+            let (Some(x), Some(y)) = (cell.0.checked_add_signed(dx), cell.1.checked_add_signed(dy)) else {
+                continue;
+            };
+            let next = (x, y);
+            if !terrain.is_walkable(x, y) || occupied.contains(&next) {
+                continue;
+            }
+            let next_cost = g + 1;
+            if cost.get(&next).is_none_or(|best| next_cost < *best) {
+                cost.insert(next, next_cost);
+                came_from.insert(next, cell);
+                open.push(Reverse((next_cost + heuristic(next), Reverse(next_cost), next)));
+            }
+        }
+    }
+
+    None
+}
+
+// Route for our own unit: everyone else's cell counts as blocked
+pub fn plan(state: &GameState, from: (u32, u32), to: (u32, u32)) -> Option<Vec<(u32, u32)>> {
+    let occupied: HashSet<(u32, u32)> = state.players.values()
+        .filter(|player| player.username != state.username)
+        .map(|player| (player.x, player.y))
+        .collect();
+    find_path(&state.terrain, &occupied, from, to)
+}
+
+fn reconstruct(came_from: &HashMap<(u32, u32), (u32, u32)>, from: (u32, u32), to: (u32, u32)) -> Vec<(u32, u32)> {
+    let mut path = vec![to];
+    let mut cell = to;
+    while let Some(&previous) = came_from.get(&cell) {
+        if previous == from {
+            break;
+        }
+        path.push(previous);
+        cell = previous;
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::grid::MapSize;
+    use crate::game::protocol::{Player, TerrainEncoding};
+
+    // One string per row: p plains, r rock, w water, i iron vein
+    fn terrain(rows: &[&str]) -> TileMap {
+        let data: Vec<String> = rows.concat().chars().map(String::from).collect();
+        TileMap::decode(rows[0].len() as u32, rows.len() as u32, TerrainEncoding::Rle, &data.join(",")).unwrap()
+    }
+
+    fn open_field(width: u32, height: u32) -> TileMap {
+        TileMap::new(MapSize::new(width, height).unwrap())
+    }
+
+    // Steps are single orthogonal moves onto walkable, unoccupied cells ending at `to`
+    fn assert_valid(terrain: &TileMap, occupied: &HashSet<(u32, u32)>, from: (u32, u32), to: (u32, u32), path: &[(u32, u32)]) {
+        assert_eq!(path.last(), Some(&to));
+        let mut previous = from;
+        for &cell in path {
+            assert_eq!(previous.0.abs_diff(cell.0) + previous.1.abs_diff(cell.1), 1, "{:?} -> {:?}", previous, cell);
+            assert!(terrain.is_walkable(cell.0, cell.1) && !occupied.contains(&cell), "{:?} is blocked", cell);
+            previous = cell;
+        }
+    }
+
+    fn manhattan(from: (u32, u32), to: (u32, u32)) -> usize {
+        (from.0.abs_diff(to.0) + from.1.abs_diff(to.1)) as usize
+    }
+
+    #[test]
+    fn same_cell_needs_no_steps() {
+        assert_eq!(find_path(&open_field(4, 4), &HashSet::new(), (1, 1), (1, 1)), Some(Vec::new()));
+    }
+
+    #[test]
+    fn long_open_field_routes_are_shortest() {
+        for (size, from, to) in [(64, (0, 0), (63, 0)), (200, (0, 0), (199, 199)), (256, (255, 0), (0, 255)), (256, (10, 200), (240, 3))] {
+            let terrain = open_field(size, size);
+            let path = find_path(&terrain, &HashSet::new(), from, to).unwrap_or_else(|| panic!("no route on {0}x{0}", size));
+            assert_eq!(path.len(), manhattan(from, to));
+            assert_valid(&terrain, &HashSet::new(), from, to, &path);
+        }
+    }
+
+    #[test]
+    fn full_size_diagonal_stays_within_budget() {
+        let terrain = open_field(1024, 1024);
+        let path = find_path(&terrain, &HashSet::new(), (0, 0), (1023, 1023)).unwrap();
+        assert_eq!(path.len(), 2 * 1023);
+    }
+
+    #[test]
+    fn routes_around_rock_and_water() {
+        let terrain = terrain(&[
+            "ppppp",
+            "prrwp",
+            "ppiwp",
+            "rwwwp",
+            "ppppp",
+        ]);
+        let (from, to) = ((2, 2), (0, 4));
+        let path = find_path(&terrain, &HashSet::new(), from, to).unwrap();
+        assert_valid(&terrain, &HashSet::new(), from, to, &path);
+        // Out through the top-left and down the right-hand side is the only way
+        assert_eq!(path.len(), 16);
+    }
+
+    #[test]
+    fn routes_around_other_players() {
+        let terrain = open_field(3, 3);
+        let occupied = HashSet::from([(1, 0), (1, 1)]);
+        let path = find_path(&terrain, &occupied, (0, 0), (2, 0)).unwrap();
+        assert_eq!(path, [(0, 1), (0, 2), (1, 2), (2, 2), (2, 1), (2, 0)]);
+    }
+
+    #[test]
+    fn occupied_or_unwalkable_targets_are_unreachable() {
+        let terrain = terrain(&["ppr", "ppw"]);
+        assert_eq!(find_path(&terrain, &HashSet::from([(1, 1)]), (0, 0), (1, 1)), None);
+        assert_eq!(find_path(&terrain, &HashSet::new(), (0, 0), (2, 0)), None);
+        assert_eq!(find_path(&terrain, &HashSet::new(), (0, 0), (2, 1)), None);
+        assert_eq!(find_path(&terrain, &HashSet::new(), (0, 0), (7, 7)), None);
+    }
+
+    #[test]
+    fn walled_off_targets_are_unreachable() {
+        let terrain = terrain(&[
+            "ppppp",
+            "prrrp",
+            "prpwp",
+            "prrrp",
+        ]);
+        assert_eq!(find_path(&terrain, &HashSet::new(), (0, 0), (2, 2)), None);
+    }
+
+    #[test]
+    fn plan_blocks_everyone_but_us() {
+        let mut state = GameState::new("ann", "arena");
+        for (username, x, y) in [("ann", 0, 0), ("bob", 1, 0)] {
+            let player = Player { username: username.into(), x, y, room: "arena".into(), health: 100, resources: 0 };
+            state.players.insert(username.into(), player);
+        }
+        let path = plan(&state, (0, 0), (2, 0)).unwrap();
+        assert!(!path.contains(&(1, 0)));
+        assert_eq!(path.len(), 4);
+        assert_eq!(plan(&state, (0, 0), (1, 0)), None);
+    }
+}
@@ -0,0 +1,199 @@
+// Client-side prediction for our own unit. Move intents are numbered and
+// applied locally right away; authoritative updates drop the inputs the server
+// has processed, replay the rest on top, and ease out any visible difference.
+
+use std::collections::VecDeque;
+
+use super::outbox::MOVE_STALE_MS;
+
+// How long a correction (or a predicted step) takes to blend in
+pub const CORRECTION_MS: f64 = 100.0;
+
+// Longer than a move may wait in the outbox, plus a generous round trip.
+// Inputs nobody acknowledged by then were lost or rejected.
+pub const INPUT_TIMEOUT_MS: f64 = MOVE_STALE_MS + 2000.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Input {
+    seq: u64,
+    x: u32,
+    y: u32,
+    issued_at: f64,
+}
+
+// Drawn position at the moment the prediction last changed
+#[derive(Debug, Clone, Copy)]
+struct Blend {
+    from_x: f64,
+    from_y: f64,
+    started_at: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct Predictor {
+    last_seq: u64,
+    inputs: VecDeque<Input>,
+    authoritative: Option<(u32, u32)>,
+    predicted: Option<(u32, u32)>,
+    blend: Option<Blend>,
+}
+
+impl Predictor {
+    // Sequence numbers keep increasing across reconnects and resets
+    pub fn next_seq(&mut self) -> u64 {
+        self.last_seq += 1;
+        self.last_seq
+    }
+
+    // Applies a move intent locally before the server has seen it. (x, y) is
+    // the cell the server will put us on next, not necessarily the target.
+    pub fn apply_input(&mut self, seq: u64, x: u32, y: u32, now: f64) {
+        self.inputs.push_back(Input { seq, x, y, issued_at: now });
+        self.repredict(now);
+    }
+
+    // Authoritative position for our unit. `ack` is the last move the server
+    // processed; without one, the first pending move ending on this cell counts.
+    pub fn reconcile(&mut self, x: u32, y: u32, ack: Option<u64>, now: f64) {
+        match ack {
+            Some(ack) => self.inputs.retain(|input| input.seq > ack),
+            None => {
+                if let Some(i) = self.inputs.iter().position(|input| (input.x, input.y) == (x, y)) {
+                    self.inputs.drain(..=i);
+                }
+            }
+        }
+        self.authoritative = Some((x, y));
+        self.repredict(now);
+    }
+
+    // Drops inputs that were never acknowledged; returns true if any were
+    pub fn expire(&mut self, now: f64) -> bool {
+        let before = self.inputs.len();
+        self.inputs.retain(|input| now - input.issued_at < INPUT_TIMEOUT_MS);
+        if self.inputs.len() == before {
+            return false;
+        }
+        self.repredict(now);
+        true
+    }
+
+    pub fn reset(&mut self) {
+        self.inputs.clear();
+        self.authoritative = None;
+        self.predicted = None;
+        self.blend = None;
+    }
+
+    // Moves sent or queued but not yet acknowledged
+    pub fn pending(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn predicted(&self) -> Option<(u32, u32)> {
+        self.predicted
+    }
+
+    // Drawn position in (fractional) cells, blending towards the prediction
+    pub fn position(&self, now: f64) -> Option<(f64, f64)> {
+        let (x, y) = self.predicted?;
+        let (x, y) = (x as f64, y as f64);
+        match self.blend {
+            Some(blend) => {
+                let t = ((now - blend.started_at) / CORRECTION_MS).clamp(0.0, 1.0);
+                Some((blend.from_x + (x - blend.from_x) * t, blend.from_y + (y - blend.from_y) * t))
+            }
+            None => Some((x, y)),
+        }
+    }
+
+    pub fn is_animating(&self, now: f64) -> bool {
+        self.blend.is_some_and(|blend| now - blend.started_at < CORRECTION_MS)
+    }
+
+    // Replays pending inputs over the authoritative position. Moves are absolute
+    // targets, so that leaves us on the newest one. When the result changes,
+    // blend from wherever the unit is drawn right now.
+    fn repredict(&mut self, now: f64) {
+        let predicted = self.inputs.back()
+            .map(|input| (input.x, input.y))
+            .or(self.authoritative);
+        if predicted == self.predicted {
+            return;
+        }
+
+        self.blend = self.position(now)
+            .map(|(from_x, from_y)| Blend { from_x, from_y, started_at: now });
+        self.predicted = predicted;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn predictor_at(x: u32, y: u32) -> Predictor {
+        let mut predictor = Predictor::default();
+        predictor.reconcile(x, y, None, 0.0);
+        predictor
+    }
+
+    #[test]
+    fn inputs_apply_before_the_server_answers() {
+        let mut predictor = predictor_at(5, 5);
+        let seq = predictor.next_seq();
+        predictor.apply_input(seq, 6, 5, 10.0);
+        assert_eq!(predictor.predicted(), Some((6, 5)));
+        assert_eq!(predictor.pending(), 1);
+        // Blends over from the old cell
+        assert_eq!(predictor.position(10.0), Some((5.0, 5.0)));
+        assert_eq!(predictor.position(10.0 + CORRECTION_MS), Some((6.0, 5.0)));
+    }
+
+    #[test]
+    fn acked_inputs_are_dropped_and_the_rest_replayed() {
+        let mut predictor = predictor_at(5, 5);
+        let first = predictor.next_seq();
+        predictor.apply_input(first, 6, 5, 0.0);
+        let second = predictor.next_seq();
+        predictor.apply_input(second, 7, 5, 0.0);
+
+        predictor.reconcile(6, 5, Some(first), 50.0);
+        assert_eq!(predictor.pending(), 1);
+        assert_eq!(predictor.predicted(), Some((7, 5)));
+
+        predictor.reconcile(7, 5, Some(second), 100.0);
+        assert_eq!(predictor.pending(), 0);
+        assert_eq!(predictor.predicted(), Some((7, 5)));
+    }
+
+    #[test]
+    fn without_acks_a_matching_position_confirms_inputs() {
+        let mut predictor = predictor_at(5, 5);
+        let seq = predictor.next_seq();
+        predictor.apply_input(seq, 6, 5, 0.0);
+
+        // An update for an older position keeps the input pending
+        predictor.reconcile(5, 5, None, 10.0);
+        assert_eq!(predictor.pending(), 1);
+
+        predictor.reconcile(6, 5, None, 20.0);
+        assert_eq!(predictor.pending(), 0);
+    }
+
+    #[test]
+    fn rejected_moves_fall_back_to_the_server_position() {
+        let mut predictor = predictor_at(5, 5);
+        let seq = predictor.next_seq();
+        predictor.apply_input(seq, 6, 5, 0.0);
+
+        predictor.reconcile(5, 5, Some(seq), 10.0);
+        assert_eq!(predictor.predicted(), Some((5, 5)));
+
+        let seq = predictor.next_seq();
+        predictor.apply_input(seq, 5, 6, 20.0);
+        assert!(!predictor.expire(20.0 + INPUT_TIMEOUT_MS - 1.0));
+        assert!(predictor.expire(20.0 + INPUT_TIMEOUT_MS));
+        assert_eq!(predictor.predicted(), Some((5, 5)));
+    }
+}
@@ -0,0 +1,188 @@
+// Wire protocol shared with the IronVein server.
+
+use serde::{Deserialize, Serialize};
+
+// Game structures
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Player {
+    pub username: String,
+    pub x: u32,
+    pub y: u32,
+    pub room: String,
+    pub health: u32,
+    pub resources: u32,
+}
+
+// WebSocket message types
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum WebSocketMessage {
+    #[serde(rename = "join")]
+    Join { username: String, room: String },
+    #[serde(rename = "message")]
+    Message {
+        username: String,
+        message: String,
+        room: String,
+        // Client nonce echoed back in the resulting chat_message
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nonce: Option<String>,
+    },
+    #[serde(rename = "chat_message")]
+    ChatMessage(ChatMessage),
+    #[serde(rename = "player_joined")]
+    PlayerJoined { username: String, x: u32, y: u32 },
+    #[serde(rename = "player_left")]
+    PlayerLeft { username: String },
+    #[serde(rename = "error")]
+    Error { message: String },
+    #[serde(rename = "move")]
+    Move {
+        username: String,
+        x: u32,
+        y: u32,
+        room: String,
+        // Client move sequence number, acknowledged in player_update
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seq: Option<u64>,
+        // Planned [x, y] waypoints ending at (x, y); servers that route
+        // moves themselves can ignore it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<Vec<(u32, u32)>>,
+    },
+    #[serde(rename = "player_update")]
+    PlayerUpdate {
+        username: String,
+        x: u32,
+        y: u32,
+        health: u32,
+        resources: u32,
+        // Last move seq the server processed for this player
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seq: Option<u64>,
+    },
+    #[serde(rename = "game_state")]
+    GameState {
+        players: Vec<Player>,
+        // Optional map dimensions, same as a map_info message
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        width: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        height: Option<u32>,
+    },
+    #[serde(rename = "map_info")]
+    MapInfo { width: u32, height: u32 },
+    // Full terrain for the map; also sets its dimensions
+    #[serde(rename = "terrain")]
+    Terrain { width: u32, height: u32, encoding: TerrainEncoding, data: String },
+    #[serde(rename = "ping")]
+    Ping { seq: u32, client_time: f64 },
+    #[serde(rename = "pong")]
+    Pong { seq: u32, client_time: f64, server_time: f64 },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerrainEncoding {
+    // Comma-separated runs like "120p,4r,3w,i" (plains, rock, water, iron vein)
+    Rle,
+    // One byte per tile, row-major: 0 plains, 1 rock, 2 water, 3 iron vein
+    Base64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub id: serde_json::Value,
+    pub username: String,
+    pub message: String,
+    pub timestamp: serde_json::Value,
+    pub room: String,
+    #[serde(default)]
+    pub nonce: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn round_trip(message: &WebSocketMessage) -> Value {
+        let json = serde_json::to_string(message).unwrap();
+        let parsed: WebSocketMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::from_str::<Value>(&json).unwrap());
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn move_omits_unset_optional_fields() {
+        let message = WebSocketMessage::Move {
+            username: "ann".into(), x: 3, y: 4, room: "lobby".into(), seq: None, path: None,
+        };
+        assert_eq!(round_trip(&message), json!({ "type": "move", "username": "ann", "x": 3, "y": 4, "room": "lobby" }));
+    }
+
+    #[test]
+    fn move_with_seq_and_path_round_trips() {
+        let message = WebSocketMessage::Move {
+            username: "ann".into(), x: 3, y: 4, room: "lobby".into(), seq: Some(7), path: Some(vec![(2, 4), (3, 4)]),
+        };
+        let value = round_trip(&message);
+        assert_eq!(value["seq"], 7);
+        assert_eq!(value["path"], json!([[2, 4], [3, 4]]));
+    }
+
+    #[test]
+    fn ping_and_pong_round_trip() {
+        assert_eq!(
+            round_trip(&WebSocketMessage::Ping { seq: 1, client_time: 12.5 }),
+            json!({ "type": "ping", "seq": 1, "client_time": 12.5 }),
+        );
+        let value = round_trip(&WebSocketMessage::Pong { seq: 1, client_time: 12.5, server_time: 99.0 });
+        assert_eq!(value["type"], "pong");
+        assert_eq!(value["server_time"], 99.0);
+    }
+
+    #[test]
+    fn server_messages_parse_without_optional_fields() {
+        let state: WebSocketMessage = serde_json::from_value(json!({
+            "type": "game_state",
+            "players": [{ "username": "ann", "x": 1, "y": 2, "room": "lobby", "health": 90, "resources": 5 }],
+        })).unwrap();
+        match state {
+            WebSocketMessage::GameState { players, width, height } => {
+                assert_eq!(players.len(), 1);
+                assert_eq!(players[0].health, 90);
+                assert_eq!((width, height), (None, None));
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        let update: WebSocketMessage = serde_json::from_value(json!({
+            "type": "player_update", "username": "ann", "x": 1, "y": 2, "health": 90, "resources": 5,
+        })).unwrap();
+        assert!(matches!(update, WebSocketMessage::PlayerUpdate { seq: None, .. }));
+    }
+
+    #[test]
+    fn chat_message_keeps_its_fields_beside_the_tag() {
+        let value = json!({
+            "type": "chat_message", "id": 1, "username": "ann", "message": "hi",
+            "timestamp": "2024-01-01T00:00:00Z", "room": "lobby", "nonce": "n1",
+        });
+        let message: WebSocketMessage = serde_json::from_value(value.clone()).unwrap();
+        match &message {
+            WebSocketMessage::ChatMessage(chat) => assert_eq!(chat.nonce.as_deref(), Some("n1")),
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert_eq!(round_trip(&message), value);
+    }
+
+    #[test]
+    fn terrain_encoding_is_lowercase() {
+        let value = round_trip(&WebSocketMessage::Terrain {
+            width: 2, height: 1, encoding: TerrainEncoding::Base64, data: "AAE=".into(),
+        });
+        assert_eq!(value["encoding"], "base64");
+        assert!(serde_json::from_value::<WebSocketMessage>(json!({ "type": "nope" })).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+// Sprite atlas metadata and unit animation state. The atlas JSON names frame
+// rectangles in the image and groups them into animations keyed
+// "<skin>.<activity>[.<facing>]", e.g. "self.walk.left" or "other.idle":
+//
+//   {
+//     "frames": { "self_walk_left_0": { "x": 0, "y": 0, "w": 16, "h": 16 }, ... },
+//     "animations": { "self.walk.left": { "frames": ["self_walk_left_0", ...], "frame_ms": 100 }, ... }
+//   }
+//
+// Skins are "self" for our unit and "other" for everyone else.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+pub const DEFAULT_FRAME_MS: f64 = 120.0;
+// How long a unit plays its hit animation after taking damage
+pub const HIT_MS: f64 = 300.0;
+
+// Drawn movement smaller than this (in cells) between frames is standing still
+const WALK_EPSILON: f64 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Frame {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Animation {
+    pub frames: Vec<String>,
+    #[serde(default = "default_frame_ms")]
+    pub frame_ms: f64,
+}
+
+fn default_frame_ms() -> f64 {
+    DEFAULT_FRAME_MS
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Idle,
+    Walk,
+    Hit,
+}
+
+impl Activity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Activity::Idle => "idle",
+            Activity::Walk => "walk",
+            Activity::Hit => "hit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facing {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Facing {
+    pub fn name(self) -> &'static str {
+        match self {
+            Facing::Up => "up",
+            Facing::Down => "down",
+            Facing::Left => "left",
+            Facing::Right => "right",
+        }
+    }
+
+    // Direction of a move, by its larger axis
+    fn of(dx: f64, dy: f64) -> Self {
+        if dx.abs() > dy.abs() {
+            if dx < 0.0 { Facing::Left } else { Facing::Right }
+        } else if dy < 0.0 {
+            Facing::Up
+        } else {
+            Facing::Down
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub activity: Activity,
+    pub facing: Facing,
+    // When the current activity started; animations play from here
+    pub since: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteAtlas {
+    frames: HashMap<String, Frame>,
+    animations: HashMap<String, Animation>,
+}
+
+impl SpriteAtlas {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let atlas: SpriteAtlas = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid sprite atlas: {}", e))?;
+
+        for (name, animation) in &atlas.animations {
+            if animation.frames.is_empty() {
+                return Err(format!("Animation {} has no frames", name));
+            }
+            if !animation.frame_ms.is_finite() || animation.frame_ms <= 0.0 {
+                return Err(format!("Animation {} has an invalid frame_ms", name));
+            }
+            if let Some(missing) = animation.frames.iter().find(|frame| !atlas.frames.contains_key(*frame)) {
+                return Err(format!("Animation {} uses unknown frame {}", name, missing));
+            }
+        }
+        Ok(atlas)
+    }
+
+    // Whether every frame lies inside an image of this size
+    pub fn fits(&self, width: f64, height: f64) -> bool {
+        self.frames.values().all(|frame| {
+            frame.x >= 0.0 && frame.y >= 0.0 && frame.w > 0.0 && frame.h > 0.0
+                && frame.x + frame.w <= width && frame.y + frame.h <= height
+        })
+    }
+
+    // Whether any animation has more than one frame, so frames change over time
+    pub fn is_animated(&self) -> bool {
+        self.animations.values().any(|animation| animation.frames.len() > 1)
+    }
+
+    // Frame to draw for a unit, falling back from the facing-specific animation
+    // to the undirected one and then to idle. None if the skin has neither.
+    pub fn frame(&self, skin: &str, pose: &Pose, now: f64) -> Option<&Frame> {
+        let animation = [pose.activity, Activity::Idle].iter()
+            .flat_map(|activity| [
+                format!("{}.{}.{}", skin, activity.name(), pose.facing.name()),
+                format!("{}.{}", skin, activity.name()),
+            ])
+            .find_map(|key| self.animations.get(&key))?;
+
+        let elapsed = (now - pose.since).max(0.0);
+        let index = (elapsed / animation.frame_ms) as usize % animation.frames.len();
+        self.frames.get(&animation.frames[index])
+    }
+}
+
+#[derive(Debug)]
+struct Tracked {
+    pose: Pose,
+    last: (f64, f64),
+}
+
+// Derives each unit's pose from how its drawn position changes between frames
+#[derive(Debug, Default)]
+pub struct Animator {
+    units: HashMap<String, Tracked>,
+}
+
+impl Animator {
+    // `position` is where the unit is drawn this frame and `hit_at` when it last
+    // took damage
+    pub fn update(&mut self, username: &str, position: (f64, f64), hit_at: Option<f64>, now: f64) -> Pose {
+        let tracked = self.units.entry(username.to_string()).or_insert(Tracked {
+            pose: Pose { activity: Activity::Idle, facing: Facing::Down, since: now },
+            last: position,
+        });
+
+        let (dx, dy) = (position.0 - tracked.last.0, position.1 - tracked.last.1);
+        tracked.last = position;
+        let moving = dx.abs() > WALK_EPSILON || dy.abs() > WALK_EPSILON;
+        if moving {
+            tracked.pose.facing = Facing::of(dx, dy);
+        }
+
+        let (activity, since) = match hit_at {
+            Some(hit_at) if now - hit_at < HIT_MS => (Activity::Hit, hit_at),
+            _ if moving => (Activity::Walk, now),
+            _ => (Activity::Idle, now),
+        };
+        // A new hit restarts the hit animation
+        if activity != tracked.pose.activity || activity == Activity::Hit {
+            tracked.pose.activity = activity;
+            tracked.pose.since = since;
+        }
+        tracked.pose
+    }
+
+    // Whether any unit was last seen walking or is still in its hit animation;
+    // idle units need no new frames
+    pub fn is_animating(&self, now: f64) -> bool {
+        self.units.values().any(|tracked| match tracked.pose.activity {
+            Activity::Walk => true,
+            Activity::Hit => now - tracked.pose.since < HIT_MS,
+            Activity::Idle => false,
+        })
+    }
+
+    // Forgets units that are no longer drawn
+    pub fn retain(&mut self, mut drawn: impl FnMut(&str) -> bool) {
+        self.units.retain(|username, _| drawn(username));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ATLAS: &str = r#"{
+        "frames": {
+            "idle": { "x": 0, "y": 0, "w": 16, "h": 16 },
+            "walk_0": { "x": 16, "y": 0, "w": 16, "h": 16 },
+            "walk_1": { "x": 32, "y": 0, "w": 16, "h": 16 },
+            "left_0": { "x": 0, "y": 16, "w": 16, "h": 16 },
+            "left_1": { "x": 16, "y": 16, "w": 16, "h": 16 }
+        },
+        "animations": {
+            "self.idle": { "frames": ["idle"] },
+            "self.walk": { "frames": ["walk_0", "walk_1"], "frame_ms": 100 },
+            "self.walk.left": { "frames": ["left_0", "left_1"], "frame_ms": 100 }
+        }
+    }"#;
+
+    fn atlas() -> SpriteAtlas {
+        SpriteAtlas::from_json(ATLAS).unwrap()
+    }
+
+    fn pose(activity: Activity, facing: Facing) -> Pose {
+        Pose { activity, facing, since: 0.0 }
+    }
+
+    fn frame_x(atlas: &SpriteAtlas, skin: &str, pose: &Pose, now: f64) -> Option<(f64, f64)> {
+        atlas.frame(skin, pose, now).map(|frame| (frame.x, frame.y))
+    }
+
+    #[test]
+    fn from_json_accepts_a_valid_atlas() {
+        let atlas = atlas();
+        assert!(atlas.is_animated());
+        assert!(atlas.fits(48.0, 32.0));
+        assert!(!atlas.fits(32.0, 32.0));
+        assert_eq!(atlas.animations["self.idle"].frame_ms, DEFAULT_FRAME_MS);
+    }
+
+    #[test]
+    fn from_json_rejects_broken_atlases() {
+        let frames = r#""frames": { "a": { "x": 0, "y": 0, "w": 1, "h": 1 } }"#;
+        for animations in [
+            r#"{ "self.idle": { "frames": [] } }"#,
+            r#"{ "self.idle": { "frames": ["a"], "frame_ms": 0 } }"#,
+            r#"{ "self.idle": { "frames": ["a"], "frame_ms": -5 } }"#,
+            r#"{ "self.idle": { "frames": ["b"] } }"#,
+        ] {
+            let json = format!(r#"{{ {}, "animations": {} }}"#, frames, animations);
+            assert!(SpriteAtlas::from_json(&json).is_err(), "{} was accepted", animations);
+        }
+        assert!(SpriteAtlas::from_json("{").is_err());
+        assert!(SpriteAtlas::from_json(r#"{ "frames": {} }"#).is_err());
+    }
+
+    #[test]
+    fn single_frame_atlas_is_not_animated() {
+        let json = r#"{ "frames": { "a": { "x": 0, "y": 0, "w": 1, "h": 1 } }, "animations": { "other.idle": { "frames": ["a"] } } }"#;
+        assert!(!SpriteAtlas::from_json(json).unwrap().is_animated());
+    }
+
+    #[test]
+    fn frames_fall_back_from_facing_to_activity_to_idle() {
+        let atlas = atlas();
+        // Exact facing
+        assert_eq!(frame_x(&atlas, "self", &pose(Activity::Walk, Facing::Left), 0.0), Some((0.0, 16.0)));
+        // No walk.up: the undirected walk
+        assert_eq!(frame_x(&atlas, "self", &pose(Activity::Walk, Facing::Up), 0.0), Some((16.0, 0.0)));
+        // No hit at all: idle
+        assert_eq!(frame_x(&atlas, "self", &pose(Activity::Hit, Facing::Down), 0.0), Some((0.0, 0.0)));
+        // Unknown skin: nothing to draw
+        assert_eq!(frame_x(&atlas, "other", &pose(Activity::Idle, Facing::Down), 0.0), None);
+    }
+
+    #[test]
+    fn frames_advance_from_when_the_pose_started() {
+        let atlas = atlas();
+        let walking = Pose { activity: Activity::Walk, facing: Facing::Right, since: 1_000.0 };
+        assert_eq!(frame_x(&atlas, "self", &walking, 1_000.0), Some((16.0, 0.0)));
+        assert_eq!(frame_x(&atlas, "self", &walking, 1_099.0), Some((16.0, 0.0)));
+        assert_eq!(frame_x(&atlas, "self", &walking, 1_100.0), Some((32.0, 0.0)));
+        // Loops
+        assert_eq!(frame_x(&atlas, "self", &walking, 1_200.0), Some((16.0, 0.0)));
+        // Before it started: first frame
+        assert_eq!(frame_x(&atlas, "self", &walking, 500.0), Some((16.0, 0.0)));
+    }
+
+    #[test]
+    fn animator_walks_while_moving_and_faces_the_move() {
+        let mut animator = Animator::default();
+        let first = animator.update("ann", (5.0, 5.0), None, 0.0);
+        assert_eq!((first.activity, first.facing), (Activity::Idle, Facing::Down));
+        assert!(!animator.is_animating(0.0));
+
+        let walking = animator.update("ann", (4.5, 5.1), None, 16.0);
+        assert_eq!((walking.activity, walking.facing, walking.since), (Activity::Walk, Facing::Left, 16.0));
+        assert!(animator.is_animating(16.0));
+
+        // Still walking: the animation keeps its start, so frames advance
+        let still_walking = animator.update("ann", (4.0, 5.1), None, 32.0);
+        assert_eq!(still_walking.since, 16.0);
+
+        // Stopped: idle again, facing kept
+        let stopped = animator.update("ann", (4.0, 5.1), None, 48.0);
+        assert_eq!((stopped.activity, stopped.facing), (Activity::Idle, Facing::Left));
+        assert!(!animator.is_animating(48.0));
+    }
+
+    #[test]
+    fn animator_plays_hit_for_a_while() {
+        let mut animator = Animator::default();
+        animator.update("ann", (1.0, 1.0), None, 0.0);
+
+        let hit = animator.update("ann", (1.0, 1.0), Some(100.0), 110.0);
+        assert_eq!((hit.activity, hit.since), (Activity::Hit, 100.0));
+        assert!(animator.is_animating(100.0 + HIT_MS - 1.0));
+        assert!(!animator.is_animating(100.0 + HIT_MS));
+
+        let over = animator.update("ann", (1.0, 1.0), Some(100.0), 100.0 + HIT_MS);
+        assert_eq!(over.activity, Activity::Idle);
+    }
+
+    #[test]
+    fn animator_forgets_units_no_longer_drawn() {
+        let mut animator = Animator::default();
+        animator.update("ann", (0.0, 0.0), None, 0.0);
+        animator.update("ann", (1.0, 0.0), None, 16.0);
+        animator.retain(|username| username != "ann");
+        assert!(!animator.is_animating(16.0));
+    }
+}
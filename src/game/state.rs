@@ -0,0 +1,203 @@
+// Client-side view of the battlefield, updated by applying server messages.
+
+use std::collections::HashMap;
+
+use super::grid::MapSize;
+use super::protocol::{Player, WebSocketMessage};
+use super::status::MAX_HEALTH;
+use super::terrain::TileMap;
+
+// Stats the server assumes for a freshly spawned unit
+const SPAWN_HEALTH: u32 = MAX_HEALTH;
+const SPAWN_RESOURCES: u32 = 0;
+
+#[derive(Debug, Default)]
+pub struct GameState {
+    pub username: String,
+    pub room: String,
+    pub players: HashMap<String, Player>,
+    pub map: MapSize,
+    // Always sized to `map`; plains until the server sends terrain
+    pub terrain: TileMap,
+    // Bumped on every change, so renderers can tell whether to redraw
+    pub revision: u64,
+    // Bumped only when the map or terrain changes
+    pub static_revision: u64,
+}
+
+impl GameState {
+    pub fn new(username: &str, room: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            room: room.to_string(),
+            players: HashMap::new(),
+            map: MapSize::default(),
+            terrain: TileMap::default(),
+            revision: 0,
+            static_revision: 0,
+        }
+    }
+
+    // Reducer for server messages. Returns true when the player set or map changed;
+    // chat, errors and protocol messages are left to the caller.
+    pub fn apply(&mut self, message: &WebSocketMessage) -> bool {
+        let changed = match message {
+            WebSocketMessage::PlayerJoined { username, x, y } => {
+                self.upsert_player(username, *x, *y, SPAWN_HEALTH, SPAWN_RESOURCES);
+                true
+            }
+            WebSocketMessage::PlayerUpdate { username, x, y, health, resources, .. } => {
+                self.upsert_player(username, *x, *y, *health, *resources);
+                true
+            }
+            WebSocketMessage::PlayerLeft { username } => {
+                self.players.remove(username).is_some()
+            }
+            WebSocketMessage::GameState { players, width, height } => {
+                self.players = players.iter()
+                    .map(|player| (player.username.clone(), player.clone()))
+                    .collect();
+                if let (Some(width), Some(height)) = (width, height) {
+                    self.set_map(*width, *height);
+                }
+                true
+            }
+            WebSocketMessage::MapInfo { width, height } => self.set_map(*width, *height),
+            WebSocketMessage::Terrain { width, height, encoding, data } => {
+                match TileMap::decode(*width, *height, *encoding, data) {
+                    Ok(terrain) => {
+                        self.map = terrain.size;
+                        self.terrain = terrain;
+                        self.static_revision += 1;
+                        true
+                    }
+                    Err(_) => false,
+                }
+            }
+            _ => false,
+        };
+
+        if changed {
+            self.revision += 1;
+        }
+        changed
+    }
+
+    // Ignores dimensions outside 1..=MAX_GRID_SIZE; returns true when the map changed
+    pub fn set_map(&mut self, width: u32, height: u32) -> bool {
+        match MapSize::new(width, height) {
+            Some(map) if map != self.map => {
+                self.map = map;
+                self.terrain = TileMap::new(map);
+                self.static_revision += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn upsert_player(&mut self, username: &str, x: u32, y: u32, health: u32, resources: u32) {
+        let player = Player {
+            username: username.to_string(),
+            x, y, health, resources,
+            room: self.room.clone(),
+        };
+        self.players.insert(username.to_string(), player);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::grid::MAX_GRID_SIZE;
+
+    fn player(username: &str, x: u32, y: u32) -> Player {
+        Player { username: username.to_string(), x, y, room: "arena".to_string(), health: 80, resources: 3 }
+    }
+
+    fn update(username: &str, x: u32, y: u32, health: u32) -> WebSocketMessage {
+        WebSocketMessage::PlayerUpdate { username: username.to_string(), x, y, health, resources: 5, seq: None }
+    }
+
+    #[test]
+    fn player_joined_spawns_with_full_health() {
+        let mut state = GameState::new("alice", "arena");
+        assert!(state.apply(&WebSocketMessage::PlayerJoined { username: "bob".to_string(), x: 3, y: 4 }));
+
+        let bob = &state.players["bob"];
+        assert_eq!((bob.x, bob.y), (3, 4));
+        assert_eq!((bob.health, bob.resources), (SPAWN_HEALTH, SPAWN_RESOURCES));
+        assert_eq!(bob.room, "arena");
+        assert_eq!(state.revision, 1);
+    }
+
+    #[test]
+    fn player_update_inserts_and_overwrites() {
+        let mut state = GameState::new("alice", "arena");
+        assert!(state.apply(&update("bob", 1, 1, 90)));
+        assert!(state.apply(&update("bob", 2, 1, 70)));
+
+        let bob = &state.players["bob"];
+        assert_eq!((bob.x, bob.y, bob.health, bob.resources), (2, 1, 70, 5));
+        assert_eq!(state.players.len(), 1);
+        assert_eq!(state.revision, 2);
+    }
+
+    #[test]
+    fn player_left_removes_known_players_only() {
+        let mut state = GameState::new("alice", "arena");
+        state.apply(&update("bob", 1, 1, 90));
+
+        let left = WebSocketMessage::PlayerLeft { username: "bob".to_string() };
+        assert!(state.apply(&left));
+        assert!(state.players.is_empty());
+        // Leaving twice changes nothing and doesn't force a redraw
+        assert!(!state.apply(&left));
+        assert_eq!(state.revision, 2);
+    }
+
+    #[test]
+    fn game_state_replaces_players_and_resizes_map() {
+        let mut state = GameState::new("alice", "arena");
+        state.apply(&update("carol", 0, 0, 50));
+
+        let snapshot = WebSocketMessage::GameState {
+            players: vec![player("alice", 1, 2), player("bob", 5, 6)],
+            width: Some(32),
+            height: Some(16),
+        };
+        assert!(state.apply(&snapshot));
+
+        let mut names: Vec<_> = state.players.keys().cloned().collect();
+        names.sort();
+        assert_eq!(names, ["alice", "bob"]);
+        assert_eq!(state.map, MapSize { width: 32, height: 16 });
+        assert_eq!(state.terrain.size, state.map);
+        assert_eq!(state.static_revision, 1);
+    }
+
+    #[test]
+    fn game_state_without_dimensions_keeps_map() {
+        let mut state = GameState::new("alice", "arena");
+        let snapshot = WebSocketMessage::GameState { players: vec![player("bob", 1, 1)], width: None, height: None };
+        assert!(state.apply(&snapshot));
+        assert_eq!(state.map, MapSize::default());
+        assert_eq!(state.static_revision, 0);
+    }
+
+    #[test]
+    fn invalid_map_sizes_are_ignored() {
+        let mut state = GameState::new("alice", "arena");
+        assert!(!state.set_map(0, 10));
+        assert!(!state.set_map(10, MAX_GRID_SIZE + 1));
+        assert!(!state.set_map(64, 64));
+        assert_eq!(state.map, MapSize::default());
+    }
+
+    #[test]
+    fn other_messages_leave_state_alone() {
+        let mut state = GameState::new("alice", "arena");
+        assert!(!state.apply(&WebSocketMessage::Error { message: "nope".to_string() }));
+        assert_eq!(state.revision, 0);
+    }
+}
@@ -0,0 +1,199 @@
+// Unit status shown on the map: health and resource readouts, the low-health
+// warning, and floating numbers for damage and healing between updates.
+
+use std::collections::HashMap;
+
+use super::protocol::Player;
+
+pub const MAX_HEALTH: u32 = 100;
+// At or below this share of MAX_HEALTH a unit shows the low-health warning
+pub const LOW_HEALTH_FRACTION: f64 = 0.25;
+pub const FLOATING_NUMBER_MS: f64 = 1_000.0;
+// The low-health outline pulses this long after health last changed, then holds steady
+pub const WARNING_PULSE_LIMIT_MS: f64 = 5_000.0;
+
+pub fn health_fraction(health: u32) -> f64 {
+    (health as f64 / MAX_HEALTH as f64).clamp(0.0, 1.0)
+}
+
+pub fn is_low_health(health: u32) -> bool {
+    health_fraction(health) <= LOW_HEALTH_FRACTION
+}
+
+// Low but still alive; dead units get no warning outline
+pub fn shows_warning(health: u32) -> bool {
+    health > 0 && is_low_health(health)
+}
+
+// Health bar fill: green, then yellow, then red at low health
+pub fn health_color(fraction: f64) -> &'static str {
+    if fraction > 0.6 {
+        "#2ecc71"
+    } else if fraction > LOW_HEALTH_FRACTION {
+        "#f1c40f"
+    } else {
+        "#e74c3c"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatingNumber {
+    pub username: String,
+    // Negative for damage, positive for healing
+    pub delta: i64,
+    // Cell the unit was in, for when it is no longer on the map
+    pub x: u32,
+    pub y: u32,
+    pub spawned_at: f64,
+}
+
+impl FloatingNumber {
+    pub fn text(&self) -> String {
+        format!("{:+}", self.delta)
+    }
+
+    // 0.0 when spawned, 1.0 when gone
+    pub fn progress(&self, now: f64) -> f64 {
+        ((now - self.spawned_at) / FLOATING_NUMBER_MS).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StatusEffects {
+    floating: Vec<FloatingNumber>,
+    // When each player's health last changed, while its warning may still pulse
+    health_changed: HashMap<String, f64>,
+}
+
+impl StatusEffects {
+    // Compares successive updates of the same player
+    pub fn compare(&mut self, before: &Player, after: &Player, now: f64) {
+        let delta = after.health as i64 - before.health as i64;
+        if delta != 0 {
+            self.health_changed.insert(after.username.clone(), now);
+            self.floating.push(FloatingNumber {
+                username: after.username.clone(),
+                delta,
+                x: after.x,
+                y: after.y,
+                spawned_at: now,
+            });
+        }
+    }
+
+    // When the player last took damage, while its number is still shown
+    pub fn last_hit(&self, username: &str) -> Option<f64> {
+        self.floating.iter()
+            .filter(|number| number.username == username && number.delta < 0)
+            .map(|number| number.spawned_at)
+            .reduce(f64::max)
+    }
+
+    // Whether the player's low-health outline is still pulsing rather than steady
+    pub fn is_pulsing(&self, player: &Player, now: f64) -> bool {
+        shows_warning(player.health)
+            && self.health_changed.get(&player.username).is_some_and(|at| now - at < WARNING_PULSE_LIMIT_MS)
+    }
+
+    pub fn floating(&self) -> &[FloatingNumber] {
+        &self.floating
+    }
+
+    pub fn prune(&mut self, now: f64) {
+        self.floating.retain(|number| number.progress(now) < 1.0);
+        self.health_changed.retain(|_, at| now - *at < WARNING_PULSE_LIMIT_MS);
+    }
+
+    pub fn clear(&mut self) {
+        self.floating.clear();
+        self.health_changed.clear();
+    }
+
+    pub fn is_animating(&self, now: f64) -> bool {
+        self.floating.iter().any(|number| number.progress(now) < 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(health: u32) -> Player {
+        Player { username: "bob".to_string(), x: 4, y: 2, room: "arena".to_string(), health, resources: 0 }
+    }
+
+    #[test]
+    fn health_colour_thresholds() {
+        assert_eq!(health_color(health_fraction(100)), "#2ecc71");
+        assert_eq!(health_color(health_fraction(61)), "#2ecc71");
+        assert_eq!(health_color(health_fraction(60)), "#f1c40f");
+        assert_eq!(health_color(health_fraction(26)), "#f1c40f");
+        assert_eq!(health_color(health_fraction(25)), "#e74c3c");
+        assert_eq!(health_color(health_fraction(0)), "#e74c3c");
+    }
+
+    #[test]
+    fn fraction_is_clamped_to_max_health() {
+        assert_eq!(health_fraction(50), 0.5);
+        assert_eq!(health_fraction(MAX_HEALTH * 3), 1.0);
+    }
+
+    #[test]
+    fn dead_units_show_no_warning() {
+        assert!(is_low_health(0));
+        assert!(!shows_warning(0));
+        assert!(shows_warning(1));
+        assert!(shows_warning(25));
+        assert!(!shows_warning(26));
+    }
+
+    #[test]
+    fn damage_and_healing_float_signed_deltas() {
+        let mut effects = StatusEffects::default();
+        effects.compare(&unit(80), &unit(55), 100.0);
+        effects.compare(&unit(55), &unit(55), 150.0);
+        effects.compare(&unit(55), &unit(65), 200.0);
+
+        let texts: Vec<String> = effects.floating().iter().map(FloatingNumber::text).collect();
+        assert_eq!(texts, ["-25", "+10"]);
+        assert_eq!((effects.floating()[0].x, effects.floating()[0].y), (4, 2));
+        // Healing is not a hit
+        assert_eq!(effects.last_hit("bob"), Some(100.0));
+        assert_eq!(effects.last_hit("ann"), None);
+    }
+
+    #[test]
+    fn floating_numbers_fade_out_and_are_pruned() {
+        let mut effects = StatusEffects::default();
+        effects.compare(&unit(80), &unit(70), 0.0);
+        assert_eq!(effects.floating()[0].progress(FLOATING_NUMBER_MS / 2.0), 0.5);
+        assert!(effects.is_animating(FLOATING_NUMBER_MS - 1.0));
+        assert!(!effects.is_animating(FLOATING_NUMBER_MS));
+
+        effects.prune(FLOATING_NUMBER_MS);
+        assert!(effects.floating().is_empty());
+        assert_eq!(effects.last_hit("bob"), None);
+    }
+
+    #[test]
+    fn warning_pulses_only_for_a_while_after_health_changes() {
+        let mut effects = StatusEffects::default();
+        // Low from the start: steady outline, nothing to animate
+        assert!(!effects.is_pulsing(&unit(20), 0.0));
+
+        effects.compare(&unit(40), &unit(20), 1_000.0);
+        assert!(effects.is_pulsing(&unit(20), 1_000.0 + WARNING_PULSE_LIMIT_MS - 1.0));
+        assert!(!effects.is_pulsing(&unit(20), 1_000.0 + WARNING_PULSE_LIMIT_MS));
+
+        effects.prune(1_000.0 + WARNING_PULSE_LIMIT_MS);
+        assert!(!effects.is_pulsing(&unit(20), 1_000.0));
+    }
+
+    #[test]
+    fn dead_or_healthy_units_never_pulse() {
+        let mut effects = StatusEffects::default();
+        effects.compare(&unit(20), &unit(0), 0.0);
+        assert!(!effects.is_pulsing(&unit(0), 10.0));
+        assert!(!effects.is_pulsing(&unit(90), 10.0));
+    }
+}
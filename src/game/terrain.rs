@@ -0,0 +1,238 @@
+// Terrain tile map sent by the server, and the walkability hints derived from it.
+
+use super::grid::MapSize;
+use super::protocol::TerrainEncoding;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Terrain {
+    #[default]
+    Plains,
+    Rock,
+    Water,
+    IronVein,
+}
+
+impl Terrain {
+    // One byte per tile in base64 payloads
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Terrain::Plains),
+            1 => Some(Terrain::Rock),
+            2 => Some(Terrain::Water),
+            3 => Some(Terrain::IronVein),
+            _ => None,
+        }
+    }
+
+    // Run letters in RLE payloads
+    pub fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'p' => Some(Terrain::Plains),
+            'r' => Some(Terrain::Rock),
+            'w' => Some(Terrain::Water),
+            'i' => Some(Terrain::IronVein),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Terrain::Plains => "Plains",
+            Terrain::Rock => "Rock",
+            Terrain::Water => "Water",
+            Terrain::IronVein => "Iron vein",
+        }
+    }
+
+    // The server has the final say; this only saves obviously rejected moves
+    pub fn is_walkable(&self) -> bool {
+        !matches!(self, Terrain::Rock | Terrain::Water)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TileMap {
+    pub size: MapSize,
+    tiles: Vec<Terrain>,
+}
+
+impl Default for TileMap {
+    fn default() -> Self {
+        Self::new(MapSize::default())
+    }
+}
+
+impl TileMap {
+    // All plains, which is what the world looks like until terrain arrives
+    pub fn new(size: MapSize) -> Self {
+        Self {
+            size,
+            tiles: vec![Terrain::Plains; (size.width * size.height) as usize],
+        }
+    }
+
+    pub fn decode(width: u32, height: u32, encoding: TerrainEncoding, data: &str) -> Result<Self, String> {
+        let size = MapSize::new(width, height)
+            .ok_or_else(|| format!("Invalid terrain size {}x{}", width, height))?;
+
+        let expected = (width * height) as usize;
+        let tiles = match encoding {
+            TerrainEncoding::Rle => decode_rle(data, expected)?,
+            TerrainEncoding::Base64 => decode_base64(data)?
+                .into_iter()
+                .map(|code| Terrain::from_code(code).ok_or_else(|| format!("Unknown terrain code {}", code)))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+
+        if tiles.len() != expected {
+            return Err(format!("Terrain has {} tiles, expected {}", tiles.len(), expected));
+        }
+
+        Ok(Self { size, tiles })
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<Terrain> {
+        if !self.size.contains(x, y) {
+            return None;
+        }
+        self.tiles.get((y * self.size.width + x) as usize).copied()
+    }
+
+    // Cells outside the map are never walkable
+    pub fn is_walkable(&self, x: u32, y: u32) -> bool {
+        self.get(x, y).is_some_and(|terrain| terrain.is_walkable())
+    }
+
+    // Row-major (x, y, terrain) for every tile that isn't plains
+    pub fn features(&self) -> impl Iterator<Item = (u32, u32, Terrain)> + '_ {
+        let width = self.size.width;
+        self.tiles.iter().enumerate()
+            .filter(|(_, terrain)| **terrain != Terrain::Plains)
+            .map(move |(i, terrain)| (i as u32 % width, i as u32 / width, *terrain))
+    }
+}
+
+// Comma-separated runs such as "120p,4r,3w,i": a count (default 1) then a
+// terrain letter. Fails as soon as the runs add up to more than `limit` tiles.
+fn decode_rle(data: &str, limit: usize) -> Result<Vec<Terrain>, String> {
+    let mut tiles = Vec::new();
+
+    for run in data.split(',').map(str::trim).filter(|run| !run.is_empty()) {
+        let letter = run.chars().last().unwrap_or_default();
+        let terrain = Terrain::from_letter(letter)
+            .ok_or_else(|| format!("Unknown terrain letter in run '{}'", run))?;
+        let count = &run[..run.len() - letter.len_utf8()];
+        let count: usize = if count.is_empty() {
+            1
+        } else {
+            count.parse().map_err(|_| format!("Invalid run length in '{}'", run))?
+        };
+
+        // Checked before allocating; written so a huge count can't overflow the sum
+        if count > limit - tiles.len() {
+            return Err(format!("Terrain runs exceed {} tiles", limit));
+        }
+        tiles.extend(std::iter::repeat_n(terrain, count));
+    }
+
+    Ok(tiles)
+}
+
+// Standard base64 alphabet; padding and whitespace are ignored
+fn decode_base64(data: &str) -> Result<Vec<u8>, String> {
+    fn value(byte: u8) -> Option<u32> {
+        match byte {
+            b'A'..=b'Z' => Some((byte - b'A') as u32),
+            b'a'..=b'z' => Some((byte - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((byte - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in data.bytes().filter(|byte| !byte.is_ascii_whitespace() && *byte != b'=') {
+        let value = value(byte).ok_or_else(|| format!("Invalid base64 character '{}'", byte as char))?;
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rle_decodes_runs_with_default_counts() {
+        let map = TileMap::decode(3, 2, TerrainEncoding::Rle, "2p, r,w,1i,p").unwrap();
+        assert_eq!(map.get(0, 0), Some(Terrain::Plains));
+        assert_eq!(map.get(2, 0), Some(Terrain::Rock));
+        assert_eq!(map.get(0, 1), Some(Terrain::Water));
+        assert_eq!(map.get(1, 1), Some(Terrain::IronVein));
+        assert_eq!(map.get(3, 0), None);
+        assert!(!map.is_walkable(2, 0));
+        assert!(map.is_walkable(1, 1));
+    }
+
+    #[test]
+    fn rle_rejects_malformed_runs() {
+        assert!(decode_rle("3x", 10).is_err());
+        assert!(decode_rle("-1p", 10).is_err());
+        assert!(decode_rle("1.5p", 10).is_err());
+        assert!(decode_rle("99999999999999999999999p", 10).is_err());
+    }
+
+    #[test]
+    fn rle_rejects_runs_past_the_limit_without_overflowing() {
+        assert!(decode_rle("4p", 4).is_ok());
+        assert!(decode_rle("4p,p", 4).is_err());
+        assert!(decode_rle("p,4294967295p", 4).is_err());
+        assert!(decode_rle(&format!("p,{}p", usize::MAX), 4).is_err());
+    }
+
+    #[test]
+    fn rle_tile_count_must_match_the_map() {
+        assert!(TileMap::decode(2, 2, TerrainEncoding::Rle, "3p").is_err());
+        assert!(TileMap::decode(2, 2, TerrainEncoding::Rle, "5p").is_err());
+        assert!(TileMap::decode(0, 2, TerrainEncoding::Rle, "").is_err());
+    }
+
+    #[test]
+    fn base64_decodes_codes_row_major() {
+        // [0, 1, 2, 3]
+        let map = TileMap::decode(2, 2, TerrainEncoding::Base64, "AAECAw==").unwrap();
+        assert_eq!(map.get(0, 0), Some(Terrain::Plains));
+        assert_eq!(map.get(1, 0), Some(Terrain::Rock));
+        assert_eq!(map.get(0, 1), Some(Terrain::Water));
+        assert_eq!(map.get(1, 1), Some(Terrain::IronVein));
+        assert_eq!(map.features().count(), 3);
+    }
+
+    #[test]
+    fn base64_ignores_padding_and_whitespace() {
+        assert_eq!(decode_base64("AA EC\nAw").unwrap(), [0, 1, 2, 3]);
+        assert_eq!(decode_base64("TWFu").unwrap(), b"Man");
+        assert_eq!(decode_base64("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn base64_rejects_malformed_input() {
+        assert!(decode_base64("AA*C").is_err());
+        assert!(decode_base64("AAÉC").is_err());
+        // Decodes, but 4 is not a terrain code
+        assert!(TileMap::decode(1, 1, TerrainEncoding::Base64, "BA==").is_err());
+        // Wrong number of tiles
+        assert!(TileMap::decode(2, 2, TerrainEncoding::Base64, "AAEC").is_err());
+    }
+}
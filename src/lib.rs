@@ -1,11 +1,37 @@
+mod event_bus;
+mod game;
+mod listeners;
+mod renderer;
+mod sprite_sheet;
+
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::*;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use chrono::Timelike;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use serde::Serialize;
+
+use event_bus::EventBus;
+use listeners::Listeners;
+use renderer::Renderer;
+use sprite_sheet::SpriteSheet;
+use game::camera::Camera;
+use game::chat::{ChatStatus, PendingChats, CHAT_ACK_TIMEOUT_MS};
+use game::connection::{normalize_server_url, ConnectionState, ReconnectState};
+use game::events::ClientEvent;
+use game::gesture::{Gesture, GestureTracker, TouchPhase, LONG_PRESS_MS};
+use game::grid;
+use game::hover::Hover;
+use game::minimap::Minimap;
+use game::pathfinding;
+use game::keymap::{Action, Keymap};
+use game::motion::Motion;
+use game::latency::{ClockSync, LatencyTracker};
+use game::mode::ClientMode;
+use game::outbox::{DiscardReason, Discarded, Outbox};
+use game::protocol::{ChatMessage, Player, WebSocketMessage};
+use game::state::GameState;
 
 // Import the `console.log` function from the Web API
 #[wasm_bindgen]
@@ -19,66 +45,170 @@ macro_rules! console_log {
     ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
 }
 
-// Game constants
-const GRID_SIZE: u32 = 64;
-const CELL_SIZE: u32 = 16;
-const CANVAS_SIZE: u32 = GRID_SIZE * CELL_SIZE;
+// Result of handing an outbound message to the socket
+enum Delivery {
+    Sent,
+    Queued,
+    Dropped,
+}
 
-// Game structures
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Player {
-    username: String,
-    x: u32,
-    y: u32,
-    room: String,
-    health: u32,
-    resources: u32,
+// requestAnimationFrame callback, shared with itself so each frame can schedule the next
+type FrameCallback = Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>>;
+
+// Canvas pixels a press may move before it counts as a drag rather than a click
+const DRAG_THRESHOLD_PX: f64 = 4.0;
+
+// Held movement keys step at most this often, about the server's update rate
+const KEY_STEP_INTERVAL_MS: f64 = 100.0;
+
+const KEYMAP_STORAGE_KEY: &str = "ironvein.keymap";
+
+// In-progress mouse drag on the canvas
+#[derive(Default)]
+struct DragState {
+    active: bool,
+    last_x: f64,
+    last_y: f64,
+    distance: f64,
 }
 
-// WebSocket message types
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(tag = "type")]
-enum WebSocketMessage {
-    #[serde(rename = "join")]
-    Join { username: String, room: String },
-    #[serde(rename = "message")]
-    Message { username: String, message: String, room: String },
-    #[serde(rename = "chat_message")]
-    ChatMessage(ChatMessage),
-    #[serde(rename = "player_joined")]
-    PlayerJoined { username: String, x: u32, y: u32 },
-    #[serde(rename = "player_left")]
-    PlayerLeft { username: String },
-    #[serde(rename = "error")]
-    Error { message: String },
-    #[serde(rename = "move")]
-    Move { username: String, x: u32, y: u32, room: String },
-    #[serde(rename = "player_update")]
-    PlayerUpdate { username: String, x: u32, y: u32, health: u32, resources: u32 },
-    #[serde(rename = "game_state")]
-    GameState { players: Vec<Player> },
+// Server endpoints
+const DEFAULT_SERVER_URL: &str = "wss://ironvein-server-production.up.railway.app";
+const BUILD_SERVER_URL: Option<&str> = option_env!("VITE_SERVER_URL");
+
+// Shared handles captured by WebSocket callbacks so a reconnect can
+// re-open the socket without going through the JS-side client instance
+#[derive(Clone)]
+struct SocketContext {
+    username: String,
+    room: String,
+    server_url: String,
+    // Join the battle as soon as the socket opens
+    auto_join: bool,
+    websocket: Rc<RefCell<Option<WebSocket>>>,
+    pending_messages: Rc<RefCell<PendingChats>>,
+    outbox: Rc<RefCell<Outbox>>,
+    reconnect: Rc<RefCell<ReconnectState>>,
+    connection_state: Rc<Cell<ConnectionState>>,
+    mode: Rc<Cell<ClientMode>>,
+    latency: Rc<RefCell<LatencyTracker>>,
+    clock: Rc<RefCell<ClockSync>>,
+    state: Rc<RefCell<GameState>>,
+    motion: Rc<RefCell<Motion>>,
+    events: EventBus,
+    // Handlers on the current socket; replaced when a new socket opens
+    socket_listeners: Rc<RefCell<Listeners>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ChatMessage {
-    id: serde_json::Value,
+// Shared handles for issuing move orders from DOM handlers
+#[derive(Clone)]
+struct MoveSender {
     username: String,
-    message: String,
-    timestamp: serde_json::Value,
     room: String,
+    mode: Rc<Cell<ClientMode>>,
+    websocket: Rc<RefCell<Option<WebSocket>>>,
+    outbox: Rc<RefCell<Outbox>>,
+    connection_state: Rc<Cell<ConnectionState>>,
+    pending_messages: Rc<RefCell<PendingChats>>,
+    motion: Rc<RefCell<Motion>>,
+    events: EventBus,
+}
+
+impl MoveSender {
+    // Sends (or queues) a numbered move, with the waypoints to follow if planned,
+    // and starts walking there locally. Returns whether the move is on its way.
+    // A routed move is only predicted to its first waypoint: the server walks
+    // the rest at its own pace, and later steps follow its updates.
+    fn send(&self, x: u32, y: u32, path: Option<Vec<(u32, u32)>>) -> bool {
+        let step = path.as_ref()
+            .and_then(|path| path.first().copied())
+            .unwrap_or((x, y));
+        self.send_move(x, y, path, Some(step))
+    }
+
+    // For a target we found no route to: the server may know better, but there
+    // is no first step to predict, so the unit waits for its updates
+    fn send_unplanned(&self, x: u32, y: u32) -> bool {
+        self.send_move(x, y, None, None)
+    }
+
+    fn send_move(&self, x: u32, y: u32, path: Option<Vec<(u32, u32)>>, step: Option<(u32, u32)>) -> bool {
+        if !self.mode.get().can_command() {
+            console_log!("❌ Join the battle before sending move commands");
+            return false;
+        }
+
+        let seq = self.motion.borrow_mut().prediction.next_seq();
+        let move_message = WebSocketMessage::Move {
+            username: self.username.clone(),
+            x,
+            y,
+            room: self.room.clone(),
+            seq: Some(seq),
+            path,
+        };
+        match IronVeinClient::send_or_queue(move_message, &self.websocket, &self.outbox, &self.connection_state, &self.pending_messages, &self.events) {
+            Delivery::Sent => {}
+            Delivery::Queued => console_log!("📥 Move command queued until reconnected"),
+            Delivery::Dropped => {
+                console_log!("❌ WebSocket not connected, cannot send move command");
+                return false;
+            }
+        }
+
+        if let Some((step_x, step_y)) = step {
+            self.motion.borrow_mut().prediction.apply_input(seq, step_x, step_y, js_sys::Date::now());
+        }
+        true
+    }
 }
 
 #[wasm_bindgen]
 pub struct IronVeinClient {
-    username: String,
-    room: String,
-    websocket: Option<WebSocket>,
-    players: HashMap<String, Player>,
-    my_player: Option<Player>,
-    canvas: Option<HtmlCanvasElement>,
-    context: Option<CanvasRenderingContext2d>,
-    game_loop_id: Option<i32>,
-    pending_messages: Rc<RefCell<HashMap<String, web_sys::Element>>>,
+    username: RefCell<String>,
+    room: RefCell<String>,
+    server_url: RefCell<Option<String>>,
+    websocket: Rc<RefCell<Option<WebSocket>>>,
+    state: Rc<RefCell<GameState>>,
+    // Interpolated and predicted positions the renderer draws players at
+    motion: Rc<RefCell<Motion>>,
+    // Cell under the mouse and the route preview to it
+    hover: Rc<RefCell<Hover>>,
+    // Overview in the canvas corner; clicks on it are taken before the map's
+    minimap: Rc<Cell<Minimap>>,
+    canvas: RefCell<Option<HtmlCanvasElement>>,
+    renderer: Rc<RefCell<Option<Renderer>>>,
+    // Unit sprites once `load_sprite_atlas` succeeds; squares are drawn until then
+    sprites: Rc<RefCell<Option<SpriteSheet>>>,
+    camera: Rc<RefCell<Camera>>,
+    // Set when the last press turned into a drag so the click that follows is ignored
+    suppress_click: Rc<Cell<bool>>,
+    // Frame callback while the loop is started, and the pending requestAnimationFrame id
+    game_loop: FrameCallback,
+    game_loop_id: Rc<Cell<Option<i32>>>,
+    // Paused by the host page; hidden tabs pause on their own
+    loop_paused: Rc<Cell<bool>>,
+    socket_listeners: Rc<RefCell<Listeners>>,
+    canvas_listeners: RefCell<Listeners>,
+    document_listeners: RefCell<Listeners>,
+    pending_messages: Rc<RefCell<PendingChats>>,
+    outbox: Rc<RefCell<Outbox>>,
+    reconnect: Rc<RefCell<ReconnectState>>,
+    connection_state: Rc<Cell<ConnectionState>>,
+    mode: Rc<Cell<ClientMode>>,
+    latency: Rc<RefCell<LatencyTracker>>,
+    clock: Rc<RefCell<ClockSync>>,
+    events: EventBus,
+    next_nonce: Cell<u64>,
+    // Saved to localStorage whenever it changes
+    keymap: Rc<RefCell<Keymap>>,
+    keyboard_listeners: RefCell<Listeners>,
+}
+
+impl Default for IronVeinClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[wasm_bindgen]
@@ -87,27 +217,86 @@ impl IronVeinClient {
     pub fn new() -> Self {
         console_log!("🚀 IronVein Game Engine (Rust) initialized!");
         Self {
-            username: String::new(),
-            room: String::new(),
-            websocket: None,
-            players: HashMap::new(),
-            my_player: None,
-            canvas: None,
-            context: None,
-            game_loop_id: None,
-            pending_messages: Rc::new(RefCell::new(HashMap::new())),
+            username: RefCell::new(String::new()),
+            room: RefCell::new(String::new()),
+            server_url: RefCell::new(None),
+            websocket: Rc::new(RefCell::new(None)),
+            state: Rc::new(RefCell::new(GameState::default())),
+            motion: Rc::new(RefCell::new(Motion::default())),
+            hover: Rc::new(RefCell::new(Hover::default())),
+            minimap: Rc::new(Cell::new(Minimap::default())),
+            canvas: RefCell::new(None),
+            renderer: Rc::new(RefCell::new(None)),
+            sprites: Rc::new(RefCell::new(None)),
+            camera: Rc::new(RefCell::new(Camera::default())),
+            suppress_click: Rc::new(Cell::new(false)),
+            game_loop: Rc::new(RefCell::new(None)),
+            game_loop_id: Rc::new(Cell::new(None)),
+            loop_paused: Rc::new(Cell::new(false)),
+            socket_listeners: Rc::new(RefCell::new(Listeners::default())),
+            canvas_listeners: RefCell::new(Listeners::default()),
+            document_listeners: RefCell::new(Listeners::default()),
+            keymap: Rc::new(RefCell::new(Self::load_keymap())),
+            keyboard_listeners: RefCell::new(Listeners::default()),
+            pending_messages: Rc::new(RefCell::new(PendingChats::default())),
+            outbox: Rc::new(RefCell::new(Outbox::default())),
+            reconnect: Rc::new(RefCell::new(ReconnectState::new())),
+            connection_state: Rc::new(Cell::new(ConnectionState::Disconnected)),
+            mode: Rc::new(Cell::new(ClientMode::Disconnected)),
+            latency: Rc::new(RefCell::new(LatencyTracker::default())),
+            clock: Rc::new(RefCell::new(ClockSync::default())),
+            events: EventBus::default(),
+            next_nonce: Cell::new(0),
         }
     }
 
     #[wasm_bindgen]
-    pub fn set_user_info(&mut self, username: &str, room: &str) {
-        self.username = username.to_string();
-        self.room = room.to_string();
+    pub fn set_user_info(&self, username: &str, room: &str) {
+        *self.username.borrow_mut() = username.to_string();
+        *self.room.borrow_mut() = room.to_string();
+        *self.state.borrow_mut() = GameState::new(username, room);
+        self.motion.borrow_mut().reset(username);
+        // Revisions restart with the new state
+        if let Some(ref mut renderer) = *self.renderer.borrow_mut() {
+            renderer.invalidate();
+        }
         console_log!("User info set: {} in room {}", username, room);
     }
 
+    // Subscribe to a client event: player_joined, player_updated, player_left,
+    // game_state, position, chat, error, connection_state or latency.
+    // Callbacks receive a plain object whose `type` is the event name.
+    // They may call back into the client; every export takes &self and no
+    // internal state stays borrowed while events are emitted.
+    #[wasm_bindgen]
+    pub fn on(&self, event: &str, callback: js_sys::Function) -> Result<(), JsValue> {
+        self.events.on(event, callback)
+    }
+
+    #[wasm_bindgen]
+    pub fn off(&self, event: &str, callback: Option<js_sys::Function>) -> Result<(), JsValue> {
+        self.events.off(event, callback.as_ref())
+    }
+
+    #[wasm_bindgen]
+    pub fn set_server_url(&self, server_url: &str) {
+        let server_url = server_url.trim();
+        if server_url.is_empty() {
+            *self.server_url.borrow_mut() = None;
+            console_log!("🔧 Server URL reset to default");
+        } else {
+            *self.server_url.borrow_mut() = Some(server_url.to_string());
+            console_log!("🔧 Server URL set to {}", server_url);
+        }
+    }
+
     #[wasm_bindgen]
-    pub fn setup_game_canvas(&mut self, canvas_id: &str) -> Result<(), JsValue> {
+    pub fn get_server_url(&self) -> String {
+        self.resolve_server_url()
+    }
+
+    #[wasm_bindgen]
+    pub fn setup_game_canvas(&self, canvas_id: &str) -> Result<(), JsValue> {
         let window = web_sys::window().unwrap();
         let document = window.document().unwrap();
         
@@ -116,61 +305,145 @@ impl IronVeinClient {
             .ok_or("Canvas not found")?
             .dyn_into::<HtmlCanvasElement>()?;
 
-        canvas.set_width(CANVAS_SIZE);
-        canvas.set_height(CANVAS_SIZE);
+        // Keep the canvas resolution from the page; the camera decides what is visible
+        {
+            let mut camera = self.camera.borrow_mut();
+            camera.set_viewport(canvas.width() as f64, canvas.height() as f64);
+            camera.fit();
+        }
 
         let context = canvas
             .get_context("2d")?
             .ok_or("2d context not found")?
             .dyn_into::<CanvasRenderingContext2d>()?;
 
-        self.canvas = Some(canvas);
-        self.context = Some(context);
+        *self.canvas.borrow_mut() = Some(canvas);
+        *self.renderer.borrow_mut() = Some(Renderer::new(context, self.sprites.clone()));
+        self.setup_camera_controls()?;
+        self.setup_touch_controls()?;
         
         console_log!("🎮 Game canvas setup complete!");
         Ok(())
     }
 
+    // Resize the canvas backing store, e.g. when the host layout changes
     #[wasm_bindgen]
-    pub fn connect(&mut self) -> Result<(), JsValue> {
-        let server_url = Self::get_server_url();
-        let ws_url = format!("{}/ws/{}", server_url, self.room);
-        
-        console_log!("Connecting to WebSocket: {}", ws_url);
-        let websocket = WebSocket::new(&ws_url)?;
-        
-        // Set up WebSocket event handlers
-        let username = self.username.clone();
-        let room = self.room.clone();
-        
-        // Store websocket reference for move commands
-        self.websocket = Some(websocket.clone());
-        
-        // Setup all WebSocket handlers
-        self.setup_websocket_handlers(&websocket, username, room)?;
-        
-        Ok(())
+    pub fn resize_canvas(&self, width: u32, height: u32) {
+        if let Some(ref canvas) = *self.canvas.borrow() {
+            canvas.set_width(width);
+            canvas.set_height(height);
+            self.camera.borrow_mut().set_viewport(width as f64, height as f64);
+        }
     }
 
     #[wasm_bindgen]
-    pub fn connect_to_server(&mut self) -> Result<(), JsValue> {
-        let server_url = Self::get_server_url();
-        let ws_url = format!("{}/ws/{}", server_url, self.room);
-        
-        console_log!("Connecting to WebSocket: {}", ws_url);
-        let websocket = WebSocket::new(&ws_url)?;
-        
-        // Set up WebSocket event handlers but don't auto-join
-        let username = self.username.clone();
-        let room = self.room.clone();
-        
-        // Store websocket reference
-        self.websocket = Some(websocket.clone());
-        
+    pub fn get_map_width(&self) -> u32 {
+        self.state.borrow().map.width
+    }
+
+    #[wasm_bindgen]
+    pub fn get_map_height(&self) -> u32 {
+        self.state.borrow().map.height
+    }
+
+    // Client-side hint only; the server decides whether a move is allowed
+    #[wasm_bindgen]
+    pub fn is_walkable(&self, x: u32, y: u32) -> bool {
+        self.state.borrow().terrain.is_walkable(x, y)
+    }
+
+    #[wasm_bindgen]
+    pub fn set_camera_follow(&self, enabled: bool) {
+        self.camera.borrow_mut().follow = enabled;
+    }
+
+    #[wasm_bindgen]
+    pub fn is_camera_following(&self) -> bool {
+        self.camera.borrow().follow
+    }
+
+    // Zoom around the centre of the view, e.g. from +/- buttons
+    #[wasm_bindgen]
+    pub fn zoom_camera(&self, factor: f64) {
+        let mut camera = self.camera.borrow_mut();
+        let (center_x, center_y) = (camera.viewport_width / 2.0, camera.viewport_height / 2.0);
+        camera.zoom_at(factor, center_x, center_y);
+    }
+
+    #[wasm_bindgen]
+    pub fn center_camera_on(&self, x: u32, y: u32) {
+        let (world_x, world_y) = grid::cell_center(x, y);
+        let mut camera = self.camera.borrow_mut();
+        camera.follow = false;
+        camera.center_on(world_x, world_y);
+    }
+
+    // Show the whole map again
+    #[wasm_bindgen]
+    pub fn reset_camera(&self) {
+        let mut camera = self.camera.borrow_mut();
+        camera.follow = false;
+        camera.fit();
+    }
+
+    // How far behind the newest server update players are drawn, in ms.
+    // Around one update interval is smooth; 0 snaps to every update.
+    #[wasm_bindgen]
+    pub fn set_interpolation_delay(&self, delay_ms: f64) {
+        self.motion.borrow_mut().interpolation.set_delay(delay_ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn get_interpolation_delay(&self) -> f64 {
+        self.motion.borrow().interpolation.delay_ms()
+    }
+
+    #[wasm_bindgen]
+    pub fn connect(&self) -> Result<(), JsValue> {
+        // Setup all WebSocket handlers, auto-joining the battle on open
+        self.start_connection(true)?;
+
+        // Setup click and keyboard handlers and start game loop
+        self.setup_click_handler()?;
+        self.setup_keyboard_controls()?;
+        self.start_game_loop()
+    }
+
+    #[wasm_bindgen]
+    pub fn connect_to_server(&self) -> Result<(), JsValue> {
         // Setup WebSocket handlers without auto-join
-        self.setup_websocket_handlers_lobby_only(&websocket, username, room)?;
-        
-        Ok(())
+        self.start_connection(false)
+    }
+
+    #[wasm_bindgen]
+    pub fn disconnect(&self) {
+        self.close_socket();
+        Self::set_mode(&self.mode, &self.events, ClientMode::Disconnected);
+        Self::set_connection_state(&self.connection_state, &self.events, ConnectionState::Disconnected, 0, None);
+        let discarded = self.outbox.borrow_mut().clear();
+        Self::report_discarded(discarded, &self.pending_messages, &self.events);
+        // Queued moves are gone, so stop predicting them
+        self.motion.borrow_mut().prediction.reset();
+        console_log!("🔌 Disconnected from server");
+    }
+
+    #[wasm_bindgen]
+    pub fn set_reconnect_options(&self, max_attempts: u32, base_delay_ms: u32, max_delay_ms: u32) {
+        let mut reconnect = self.reconnect.borrow_mut();
+        reconnect.max_attempts = max_attempts;
+        reconnect.base_delay_ms = base_delay_ms.max(1);
+        reconnect.max_delay_ms = max_delay_ms.max(reconnect.base_delay_ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn get_connection_state(&self) -> String {
+        self.connection_state.get().as_str().to_string()
+    }
+
+    // Messages waiting for the socket to come back
+    #[wasm_bindgen]
+    pub fn get_queued_count(&self) -> usize {
+        self.outbox.borrow().len()
     }
 
     #[wasm_bindgen]
@@ -178,675 +451,1504 @@ impl IronVeinClient {
         if !self.is_websocket_connected() {
             return Err(JsValue::from_str("Not connected to server"));
         }
-        
-        if let Some(ref websocket) = self.websocket {
+
+        let joined = if let Some(ref websocket) = *self.websocket.borrow() {
             // Send join message to spawn player
             let join_message = WebSocketMessage::Join {
-                username: self.username.clone(),
-                room: self.room.clone(),
+                username: self.username.borrow().clone(),
+                room: self.room.borrow().clone(),
             };
-            
-            if let Ok(message_json) = serde_json::to_string(&join_message) {
-                websocket.send_with_str(&message_json)?;
-                console_log!("🏠 Joined battle as {} in room {}", self.username, self.room);
-                
-                // Setup click handler and start game loop
-                let window = web_sys::window().unwrap();
-                if let Ok(game_client) = js_sys::Reflect::get(&window, &"gameClient".into()) {
-                    if let Ok(setup_fn) = js_sys::Reflect::get(&game_client, &"setup_click_handler".into()) {
-                        if let Ok(func) = setup_fn.dyn_into::<js_sys::Function>() {
-                            let _ = func.call0(&game_client);
-                        }
-                    }
-                    if let Ok(start_fn) = js_sys::Reflect::get(&game_client, &"start_game_loop".into()) {
-                        if let Ok(func) = start_fn.dyn_into::<js_sys::Function>() {
-                            let _ = func.call0(&game_client);
-                        }
-                    }
-                }
-            }
+
+            let message_json = serde_json::to_string(&join_message)
+                .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))?;
+            websocket.send_with_str(&message_json)?;
+            // Same socket handlers; the dispatcher now lets game events through
+            Self::set_mode(&self.mode, &self.events, ClientMode::InBattle);
+            console_log!("🏠 Joined battle as {} in room {}", self.username.borrow(), self.room.borrow());
+            true
+        } else {
+            false
+        };
+
+        if joined {
+            // Setup click and keyboard handlers and start game loop
+            self.setup_click_handler()?;
+            self.setup_keyboard_controls()?;
+            self.start_game_loop()?;
         }
-        
+
         Ok(())
     }
 
+    // Watch the battle without spawning a unit
+    #[wasm_bindgen]
+    pub fn spectate(&self) -> Result<(), JsValue> {
+        if !self.is_websocket_connected() {
+            return Err(JsValue::from_str("Not connected to server"));
+        }
+        if self.mode.get() == ClientMode::InBattle {
+            return Err(JsValue::from_str("Already in battle"));
+        }
+
+        Self::set_mode(&self.mode, &self.events, ClientMode::Spectating);
+        console_log!("👀 Spectating room {}", self.room.borrow());
+        self.start_game_loop()
+    }
+
+    #[wasm_bindgen]
+    pub fn get_mode(&self) -> String {
+        self.mode.get().as_str().to_string()
+    }
+
     #[wasm_bindgen]
     pub fn send_ping(&self) -> Result<(), JsValue> {
         if !self.is_websocket_connected() {
             return Ok(()); // Silent fail for pings
         }
-        
-        if let Some(ref websocket) = self.websocket {
-            // Use minimal ping payload for efficiency
-            let ping_message = WebSocketMessage::Message {
-                username: self.username.clone(),
-                message: "p".to_string(), // Minimal payload
-                room: self.room.clone(),
+
+        if let Some(ref websocket) = *self.websocket.borrow() {
+            let now = js_sys::Date::now();
+            let ping_message = WebSocketMessage::Ping {
+                seq: self.latency.borrow_mut().start_ping(now),
+                client_time: now,
             };
-            
+
             if let Ok(message_json) = serde_json::to_string(&ping_message) {
                 let _ = websocket.send_with_str(&message_json);
                 // Silent operation - no logging for pings
             }
         }
-        
+
         Ok(())
     }
 
-    fn setup_websocket_handlers(&self, websocket: &WebSocket, username: String, room: String) -> Result<(), JsValue> {
-        // Store reference to self for callbacks
-        let websocket_for_join = websocket.clone();
-        
-        // OnOpen - join room and setup game
-        let username_clone = username.clone();
-        let room_clone = room.clone();
-        let onopen_callback = Closure::wrap(Box::new(move |_event: Event| {
-            console_log!("🌐 WebSocket connected!");
-            
-            // Auto-join room
-            let join_message = WebSocketMessage::Join {
-                username: username_clone.clone(),
-                room: room_clone.clone(),
-            };
-            
-            if let Ok(message_json) = serde_json::to_string(&join_message) {
-                let _ = websocket_for_join.send_with_str(&message_json);
-                console_log!("🏠 Auto-joined room {} as {}", room_clone, username_clone);
+    #[wasm_bindgen]
+    pub fn get_latency_stats(&self) -> Result<JsValue, JsValue> {
+        let mut latency = self.latency.borrow_mut();
+        latency.expire(js_sys::Date::now());
+        serde_wasm_bindgen::to_value(&latency.stats()).map_err(JsValue::from)
+    }
+
+    #[wasm_bindgen]
+    pub fn get_server_time(&self) -> f64 {
+        Self::server_now(&self.clock)
+    }
+
+    #[wasm_bindgen]
+    pub fn get_clock_offset(&self) -> f64 {
+        self.clock.borrow().offset_ms
+    }
+
+    #[wasm_bindgen]
+    pub fn is_clock_synced(&self) -> bool {
+        self.clock.borrow().is_synced()
+    }
+
+    fn start_connection(&self, auto_join: bool) -> Result<(), JsValue> {
+        // Replace any previous socket without triggering its reconnect logic
+        self.close_socket();
+        Self::set_mode(&self.mode, &self.events, ClientMode::Disconnected);
+        self.reconnect.borrow_mut().enabled = true;
+
+        let ctx = SocketContext {
+            username: self.username.borrow().clone(),
+            room: self.room.borrow().clone(),
+            server_url: self.resolve_server_url(),
+            auto_join,
+            websocket: self.websocket.clone(),
+            pending_messages: self.pending_messages.clone(),
+            outbox: self.outbox.clone(),
+            reconnect: self.reconnect.clone(),
+            connection_state: self.connection_state.clone(),
+            mode: self.mode.clone(),
+            latency: self.latency.clone(),
+            clock: self.clock.clone(),
+            state: self.state.clone(),
+            motion: self.motion.clone(),
+            events: self.events.clone(),
+            socket_listeners: self.socket_listeners.clone(),
+        };
+
+        Self::set_connection_state(&ctx.connection_state, &ctx.events, ConnectionState::Connecting, 0, None);
+        Self::open_socket(&ctx)
+    }
+
+    fn close_socket(&self) {
+        {
+            let mut reconnect = self.reconnect.borrow_mut();
+            reconnect.enabled = false;
+            reconnect.attempt = 0;
+            if let Some(timer_id) = reconnect.timer_id.take() {
+                web_sys::window().unwrap().clear_timeout_with_handle(timer_id);
             }
-            
-            // Setup click handler and start game loop
-            let window = web_sys::window().unwrap();
-            if let Ok(game_client) = js_sys::Reflect::get(&window, &"gameClient".into()) {
-                if let Ok(setup_fn) = js_sys::Reflect::get(&game_client, &"setup_click_handler".into()) {
-                    if let Ok(func) = setup_fn.dyn_into::<js_sys::Function>() {
-                        let _ = func.call0(&game_client);
-                    }
-                }
-                if let Ok(start_fn) = js_sys::Reflect::get(&game_client, &"start_game_loop".into()) {
-                    if let Ok(func) = start_fn.dyn_into::<js_sys::Function>() {
-                        let _ = func.call0(&game_client);
-                    }
-                }
+        }
+
+        // Detach first so the old socket's close event can't trigger anything
+        self.socket_listeners.borrow_mut().detach_all();
+        if let Some(websocket) = self.websocket.borrow_mut().take() {
+            let _ = websocket.close();
+        }
+    }
+
+    fn open_socket(ctx: &SocketContext) -> Result<(), JsValue> {
+        let ws_url = format!("{}/ws/{}", ctx.server_url, ctx.room);
+
+        console_log!("Connecting to WebSocket: {}", ws_url);
+        let websocket = WebSocket::new(&ws_url)?;
+
+        // Store websocket reference for move commands
+        *ctx.websocket.borrow_mut() = Some(websocket.clone());
+
+        Self::setup_websocket_handlers(&websocket, ctx)
+    }
+
+    fn schedule_reconnect(ctx: &SocketContext) {
+        let (attempt, max_attempts, delay_ms) = {
+            let reconnect = ctx.reconnect.borrow();
+            if !reconnect.enabled {
+                drop(reconnect);
+                Self::set_connection_state(&ctx.connection_state, &ctx.events, ConnectionState::Disconnected, 0, None);
+                Self::set_mode(&ctx.mode, &ctx.events, ClientMode::Disconnected);
+                let discarded = ctx.outbox.borrow_mut().clear();
+                Self::report_discarded(discarded, &ctx.pending_messages, &ctx.events);
+                return;
+            }
+            if reconnect.exhausted() {
+                let attempts = reconnect.attempt;
+                drop(reconnect);
+                console_log!("🔌 Giving up after {} reconnect attempts", attempts);
+                ctx.reconnect.borrow_mut().enabled = false;
+                Self::set_connection_state(&ctx.connection_state, &ctx.events, ConnectionState::Disconnected, attempts, None);
+                Self::set_mode(&ctx.mode, &ctx.events, ClientMode::Disconnected);
+                let discarded = ctx.outbox.borrow_mut().clear();
+                Self::report_discarded(discarded, &ctx.pending_messages, &ctx.events);
+                return;
             }
-        }) as Box<dyn FnMut(Event)>);
-        websocket.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
-        onopen_callback.forget();
+            (reconnect.attempt + 1, reconnect.max_attempts, reconnect.next_delay_ms(js_sys::Math::random()))
+        };
 
-        // OnMessage - handle all server messages
-        let username_for_msg = username.clone();
-        let pending_messages = self.pending_messages.clone();
-        let onmessage_callback = Closure::wrap(Box::new(move |event: MessageEvent| {
-            if let Ok(message_str) = event.data().dyn_into::<js_sys::JsString>() {
-                let message_str = String::from(message_str);
-                
-                match serde_json::from_str::<WebSocketMessage>(&message_str) {
-                    Ok(parsed_message) => {
-                        match parsed_message {
-                            WebSocketMessage::PlayerJoined { username, x, y } => {
-                                console_log!("🟢 Player {} joined at ({}, {})", username, x, y);
-                                Self::update_player_list(&username, x, y, 100, 0);
-                                Self::update_game_client_player(&username, x, y, 100, 0);
-                            }
-                            WebSocketMessage::PlayerUpdate { username, x, y, health, resources } => {
-                                console_log!("🎮 Player {} moved to ({}, {})", username, x, y);
-                                Self::update_player_list(&username, x, y, health, resources);
-                                Self::update_game_client_player(&username, x, y, health, resources);
-                                Self::update_position_display(&username, x, y);
-                            }
-                            WebSocketMessage::PlayerLeft { username } => {
-                                console_log!("🔴 Player {} left", username);
-                                Self::remove_player_from_list(&username);
-                            }
-                            WebSocketMessage::GameState { players } => {
-                                console_log!("🌍 Received game state with {} players", players.len());
-                                for player in &players {
-                                    Self::update_player_list(&player.username, player.x, player.y, player.health, player.resources);
-                                }
-                                Self::update_all_game_players(&players);
-                            }
-                            WebSocketMessage::ChatMessage(chat_msg) => {
-                                // Handle ping responses with backward compatibility
-                                if (chat_msg.message == "__ping__" || chat_msg.message == "p") && chat_msg.username == username_for_msg {
-                                    Self::handle_ping_response();
-                                    return;
-                                }
-                                Self::handle_chat_message(chat_msg, &pending_messages);
-                            }
-                            WebSocketMessage::Error { message } => {
-                                console_log!("❌ Server error: {}", message);
-                                Self::append_chat_message(&format!("❌ Error: {}", message));
-                            }
-                            _ => {}
-                        }
-                    }
-                    Err(e) => {
-                        console_log!("❌ Failed to parse message: {}", e);
-                    }
+        console_log!("🔄 Reconnecting in {}ms (attempt {}/{})", delay_ms, attempt, max_attempts);
+        Self::set_connection_state(&ctx.connection_state, &ctx.events, ConnectionState::Reconnecting, attempt, Some(delay_ms));
+
+        let ctx_for_timer = ctx.clone();
+        let reconnect_callback = Closure::once_into_js(move || {
+            {
+                let mut reconnect = ctx_for_timer.reconnect.borrow_mut();
+                reconnect.timer_id = None;
+                if !reconnect.enabled {
+                    return;
                 }
             }
-        }) as Box<dyn FnMut(MessageEvent)>);
-        websocket.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
-        onmessage_callback.forget();
+            if let Err(e) = Self::open_socket(&ctx_for_timer) {
+                console_log!("❌ Reconnect attempt failed: {:?}", e);
+                Self::schedule_reconnect(&ctx_for_timer);
+            }
+        });
 
-        // OnError - handle connection errors
-        let onerror_callback = Closure::wrap(Box::new(|error_event: ErrorEvent| {
-            console_log!("❌ WebSocket connection error: {:?}", error_event);
-            Self::append_chat_message("❌ Connection error - please refresh to reconnect");
-        }) as Box<dyn FnMut(ErrorEvent)>);
-        websocket.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
-        onerror_callback.forget();
+        let window = web_sys::window().unwrap();
+        match window.set_timeout_with_callback_and_timeout_and_arguments_0(
+            reconnect_callback.unchecked_ref(),
+            delay_ms as i32,
+        ) {
+            Ok(timer_id) => {
+                let mut reconnect = ctx.reconnect.borrow_mut();
+                reconnect.attempt = attempt;
+                reconnect.timer_id = Some(timer_id);
+            }
+            Err(e) => {
+                console_log!("❌ Failed to schedule reconnect: {:?}", e);
+                Self::set_connection_state(&ctx.connection_state, &ctx.events, ConnectionState::Disconnected, attempt, None);
+                Self::set_mode(&ctx.mode, &ctx.events, ClientMode::Disconnected);
+                let discarded = ctx.outbox.borrow_mut().clear();
+                Self::report_discarded(discarded, &ctx.pending_messages, &ctx.events);
+            }
+        }
+    }
 
-        // OnClose - handle connection close
-        let onclose_callback = Closure::wrap(Box::new(|close_event: CloseEvent| {
-            console_log!("🔌 WebSocket connection closed. Code: {}, Reason: {}", 
-                close_event.code(), close_event.reason());
-            Self::append_chat_message("🔌 Connection lost. Please refresh to reconnect.");
-        }) as Box<dyn FnMut(CloseEvent)>);
-        websocket.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
-        onclose_callback.forget();
+    fn handle_socket_opened(ctx: &SocketContext) {
+        // A non-zero attempt count tells subscribers this was a reconnect. The
+        // backoff carries on until the server answers (see dispatch_message).
+        let attempts = ctx.reconnect.borrow_mut().opened(js_sys::Date::now());
+        Self::set_connection_state(&ctx.connection_state, &ctx.events, ConnectionState::Connected, attempts, None);
+    }
 
-        Ok(())
+    fn handle_socket_closed(ctx: &SocketContext, websocket: &WebSocket, close_event: &CloseEvent) {
+        console_log!("🔌 WebSocket connection closed. Code: {}, Reason: {}",
+            close_event.code(), close_event.reason());
+
+        // Ignore sockets that were already replaced or closed on purpose
+        let is_current = ctx.websocket.borrow().as_ref() == Some(websocket);
+        if !is_current {
+            return;
+        }
+
+        ctx.reconnect.borrow_mut().closed(js_sys::Date::now());
+        Self::schedule_reconnect(ctx);
     }
 
-    fn setup_websocket_handlers_lobby_only(&self, websocket: &WebSocket, username: String, room: String) -> Result<(), JsValue> {
-        // OnOpen - connect but don't auto-join battle
-        let onopen_callback = Closure::wrap(Box::new(move |_event: Event| {
-            console_log!("🌐 WebSocket connected to lobby!");
-            // Don't auto-join - user will manually join battle later
-        }) as Box<dyn FnMut(Event)>);
-        websocket.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
-        onopen_callback.forget();
+    // Replays messages queued while the socket was down, after the join went out
+    fn flush_outbox(websocket: &WebSocket, ctx: &SocketContext) {
+        let (ready, discarded) = ctx.outbox.borrow_mut().drain(js_sys::Date::now());
+        Self::report_discarded(discarded, &ctx.pending_messages, &ctx.events);
+
+        if !ready.is_empty() {
+            console_log!("📤 Flushing {} queued message(s)", ready.len());
+        }
+        for message in ready {
+            match Self::transmit(websocket, &message) {
+                Ok(()) => Self::handle_sent(&message, &ctx.pending_messages, &ctx.events),
+                Err(e) => {
+                    console_log!("❌ Failed to flush queued message: {:?}", e);
+                    Self::report_discarded(
+                        vec![Discarded { message, reason: DiscardReason::Disconnected }],
+                        &ctx.pending_messages,
+                        &ctx.events,
+                    );
+                }
+            }
+        }
+    }
+
+    fn send_join(websocket: &WebSocket, username: &str, room: &str) {
+        let join_message = WebSocketMessage::Join {
+            username: username.to_string(),
+            room: room.to_string(),
+        };
+
+        if let Ok(message_json) = serde_json::to_string(&join_message) {
+            let _ = websocket.send_with_str(&message_json);
+            console_log!("🏠 Auto-joined room {} as {}", room, username);
+        }
+    }
+
+    fn setup_websocket_handlers(websocket: &WebSocket, ctx: &SocketContext) -> Result<(), JsValue> {
+        let mut listeners = ctx.socket_listeners.borrow_mut();
+
+        // OnOpen - enter the lobby, or (re)join the battle
+        let ctx_for_open = ctx.clone();
+        let websocket_for_open = websocket.clone();
+        listeners.attach(websocket, "open", move |_event: Event| {
+            console_log!("🌐 WebSocket connected!");
+            Self::handle_socket_opened(&ctx_for_open);
+
+            // Auto-join on connect(), and resume the session after a reconnect
+            match ctx_for_open.mode.get() {
+                ClientMode::InBattle => {
+                    Self::send_join(&websocket_for_open, &ctx_for_open.username, &ctx_for_open.room);
+                }
+                ClientMode::Spectating => {}
+                ClientMode::Disconnected | ClientMode::Lobby if ctx_for_open.auto_join => {
+                    Self::send_join(&websocket_for_open, &ctx_for_open.username, &ctx_for_open.room);
+                    Self::set_mode(&ctx_for_open.mode, &ctx_for_open.events, ClientMode::InBattle);
+                }
+                ClientMode::Disconnected | ClientMode::Lobby => {
+                    // Don't auto-join - user will manually join battle later
+                    Self::set_mode(&ctx_for_open.mode, &ctx_for_open.events, ClientMode::Lobby);
+                }
+            }
+            Self::flush_outbox(&websocket_for_open, &ctx_for_open);
+        })?;
 
-        // OnMessage - handle lobby messages (chat only, no game events yet)
-        let username_for_msg = username.clone();
-        let pending_messages = self.pending_messages.clone();
-        let onmessage_callback = Closure::wrap(Box::new(move |event: MessageEvent| {
+        // OnMessage - parse and hand off to the dispatcher
+        let ctx_for_message = ctx.clone();
+        listeners.attach(websocket, "message", move |event: Event| {
+            let event: MessageEvent = event.unchecked_into();
             if let Ok(message_str) = event.data().dyn_into::<js_sys::JsString>() {
                 let message_str = String::from(message_str);
-                
+
                 match serde_json::from_str::<WebSocketMessage>(&message_str) {
-                    Ok(parsed_message) => {
-                        match parsed_message {
-                            WebSocketMessage::ChatMessage(chat_msg) => {
-                                // Handle ping responses with backward compatibility
-                                if (chat_msg.message == "__ping__" || chat_msg.message == "p") && chat_msg.username == username_for_msg {
-                                    Self::handle_ping_response();
-                                    return;
-                                }
-                                Self::handle_chat_message(chat_msg, &pending_messages);
-                            }
-                            WebSocketMessage::Error { message } => {
-                                console_log!("❌ Server error: {}", message);
-                                Self::append_chat_message(&format!("❌ Error: {}", message));
-                            }
-                            _ => {
-                                // Ignore game events in lobby mode
-                            }
-                        }
-                    }
+                    Ok(parsed_message) => Self::dispatch_message(&ctx_for_message, parsed_message),
                     Err(e) => {
                         console_log!("❌ Failed to parse message: {}", e);
                     }
                 }
             }
-        }) as Box<dyn FnMut(MessageEvent)>);
-        websocket.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
-        onmessage_callback.forget();
+        })?;
 
-        // OnError - handle connection errors
-        let onerror_callback = Closure::wrap(Box::new(|error_event: ErrorEvent| {
+        // OnError - the close event that follows drives reconnection
+        listeners.attach(websocket, "error", |error_event: Event| {
             console_log!("❌ WebSocket connection error: {:?}", error_event);
-            Self::append_chat_message("❌ Connection error - please refresh to reconnect");
-        }) as Box<dyn FnMut(ErrorEvent)>);
-        websocket.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
-        onerror_callback.forget();
+        })?;
 
         // OnClose - handle connection close
-        let onclose_callback = Closure::wrap(Box::new(|close_event: CloseEvent| {
-            console_log!("🔌 WebSocket connection closed. Code: {}, Reason: {}", 
-                close_event.code(), close_event.reason());
-            Self::append_chat_message("🔌 Connection lost. Please refresh to reconnect.");
-        }) as Box<dyn FnMut(CloseEvent)>);
-        websocket.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
-        onclose_callback.forget();
+        let ctx_for_close = ctx.clone();
+        let websocket_for_close = websocket.clone();
+        listeners.attach(websocket, "close", move |event: Event| {
+            let close_event: CloseEvent = event.unchecked_into();
+            Self::handle_socket_closed(&ctx_for_close, &websocket_for_close, &close_event);
+        })?;
 
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn setup_click_handler(&self) -> Result<(), JsValue> {
-        if let Some(ref canvas) = self.canvas {
-            let click_callback = Closure::wrap(Box::new(move |event: MouseEvent| {
-                let canvas = event.target().unwrap().dyn_into::<HtmlCanvasElement>().unwrap();
-                let rect = canvas.get_bounding_client_rect();
-                
-                let canvas_width = rect.width();
-                let canvas_height = rect.height();
-                
-                let x = ((event.client_x() as f64 - rect.x()) / canvas_width * GRID_SIZE as f64) as u32;
-                let y = ((event.client_y() as f64 - rect.y()) / canvas_height * GRID_SIZE as f64) as u32;
-                
-                if x < GRID_SIZE && y < GRID_SIZE {
-                    console_log!("🎯 Click at grid position: ({}, {})", x, y);
-                    
-                    // Send move command directly
-                    let window = web_sys::window().unwrap();
-                    if let Ok(game_client) = js_sys::Reflect::get(&window, &"gameClient".into()) {
-                        if let Ok(send_move_fn) = js_sys::Reflect::get(&game_client, &"send_move_command".into()) {
-                            if let Ok(func) = send_move_fn.dyn_into::<js_sys::Function>() {
-                                let args = js_sys::Array::new();
-                                args.push(&(x as f64).into());
-                                args.push(&(y as f64).into());
-                                let _ = func.apply(&game_client, &args);
-                            }
-                        }
-                    }
-                }
-            }) as Box<dyn FnMut(MouseEvent)>);
-
-            canvas.set_onclick(Some(click_callback.as_ref().unchecked_ref()));
-            click_callback.forget();
-            
-            console_log!("🖱️ Click handler setup complete! Click to move around the grid.");
+    // Single entry point for server messages; the current mode decides what gets through
+    fn dispatch_message(ctx: &SocketContext, message: WebSocketMessage) {
+        // The server is really talking to us, so a later drop backs off from scratch
+        if matches!(message, WebSocketMessage::GameState { .. } | WebSocketMessage::Pong { .. }) {
+            ctx.reconnect.borrow_mut().confirmed();
+        }
+        if !ctx.mode.get().accepts(&message) {
+            return; // e.g. game events while still in the lobby
         }
-        Ok(())
-    }
 
-    #[wasm_bindgen]
-    pub fn send_move_command(&self, x: u32, y: u32) -> Result<(), JsValue> {
-        if !self.is_websocket_connected() {
-            console_log!("❌ WebSocket not connected, cannot send move command");
-            return Ok(());
+        let map_before = ctx.state.borrow().map;
+        let now = js_sys::Date::now();
+        let changed = Self::apply_message(&ctx.state, &ctx.motion, &message, now);
+
+        let map = ctx.state.borrow().map;
+        if map != map_before {
+            console_log!("🗺️ Map size is now {}x{}", map.width, map.height);
+            ctx.events.emit(&ClientEvent::MapInfo(map));
         }
-        
-        if let Some(ref websocket) = self.websocket {
-            let move_message = WebSocketMessage::Move {
-                username: self.username.clone(),
-                x,
-                y,
-                room: self.room.clone(),
-            };
-            
-            if let Ok(message_json) = serde_json::to_string(&move_message) {
-                match websocket.send_with_str(&message_json) {
-                    Ok(_) => {
-                        console_log!("📤 Sent move command: ({}, {})", x, y);
-                        // Optimistic update
-                        Self::update_position_display(&self.username, x, y);
-                    }
-                    Err(e) => {
-                        console_log!("❌ Failed to send move command: {:?}", e);
-                    }
+
+        match message {
+            WebSocketMessage::PlayerJoined { username, x, y } => {
+                console_log!("🟢 Player {} joined at ({}, {})", username, x, y);
+                if username == ctx.username {
+                    ctx.motion.borrow_mut().prediction.reconcile(x, y, None, now);
+                }
+                // Cloned out first; listeners may update or remove players
+                let player = ctx.state.borrow().players.get(&username).cloned();
+                if let Some(player) = player {
+                    ctx.events.emit(&ClientEvent::PlayerJoined(player));
                 }
             }
-        }
-        Ok(())
+            WebSocketMessage::PlayerUpdate { username, x, y, seq, .. } => {
+                console_log!("🎮 Player {} moved to ({}, {})", username, x, y);
+                // Cloned out first; listeners may update or remove players
+                let player = ctx.state.borrow().players.get(&username).cloned();
+                if let Some(player) = player {
+                    ctx.events.emit(&ClientEvent::PlayerUpdated(player));
+                }
+                if username == ctx.username {
+                    Self::reconcile_position(ctx, x, y, seq, now);
+                }
+            }
+            WebSocketMessage::PlayerLeft { username } => {
+                console_log!("🔴 Player {} left", username);
+                ctx.events.emit(&ClientEvent::PlayerLeft { username });
+            }
+            WebSocketMessage::GameState { players, .. } => {
+                console_log!("🌍 Received game state with {} players", players.len());
+                if let Some(me) = players.iter().find(|player| player.username == ctx.username) {
+                    Self::reconcile_position(ctx, me.x, me.y, None, now);
+                }
+                ctx.events.emit(&ClientEvent::GameState { players });
+            }
+            WebSocketMessage::Terrain { width, height, .. } => {
+                if changed {
+                    console_log!("⛰️ Terrain loaded for {}x{} map", width, height);
+                } else {
+                    console_log!("❌ Ignoring invalid terrain payload for {}x{} map", width, height);
+                    ctx.events.emit(&ClientEvent::Error { message: "Received invalid terrain data".to_string() });
+                }
+            }
+            WebSocketMessage::ChatMessage(chat_msg) => {
+                Self::handle_chat_message(chat_msg, &ctx.pending_messages, &ctx.clock, &ctx.events);
+            }
+            WebSocketMessage::Pong { seq, client_time, server_time } => {
+                Self::handle_pong(seq, client_time, server_time, &ctx.latency, &ctx.clock, &ctx.events);
+            }
+            WebSocketMessage::Error { message } => {
+                console_log!("❌ Server error: {}", message);
+                ctx.events.emit(&ClientEvent::Error { message });
+            }
+            _ => {}
+        }
     }
 
-    #[wasm_bindgen]
-    pub fn send_message(&self, message: &str) -> Result<(), JsValue> {
-        if !self.is_websocket_connected() {
-            console_log!("❌ WebSocket not connected, cannot send message");
-            // Only show error for non-ping messages
-            if message != "__ping__" && message != "p" {
-                Self::append_chat_message("❌ Not connected to server");
+    // Applies a message to the state and keeps the drawn positions, liveness
+    // and fade-outs in step with it. Returns whether the state changed.
+    fn apply_message(state: &Rc<RefCell<GameState>>, motion: &Rc<RefCell<Motion>>, message: &WebSocketMessage, now: f64) -> bool {
+        let departing = Self::departing_players(&state.borrow(), message);
+        let updated = match message {
+            WebSocketMessage::PlayerUpdate { username, .. } => state.borrow().players.get(username).cloned(),
+            _ => None,
+        };
+        let changed = state.borrow_mut().apply(message);
+
+        let mut motion = motion.borrow_mut();
+        motion.observe(message, now);
+        for player in departing {
+            motion.depart(player, now);
+        }
+        if let (Some(before), WebSocketMessage::PlayerUpdate { username, .. }) = (updated, message) {
+            if let Some(after) = state.borrow().players.get(username) {
+                motion.status.compare(&before, after, now);
             }
-            return Ok(());
         }
-        
-        if let Some(ref websocket) = self.websocket {
-            let chat_message = WebSocketMessage::Message {
-                username: self.username.clone(),
-                message: message.to_string(),
-                room: self.room.clone(),
-            };
-            
-            let message_json = serde_json::to_string(&chat_message)
-                .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))?;
-            
-            match websocket.send_with_str(&message_json) {
-                Ok(_) => {
-                    // Only log and add to pending for non-ping messages
-                    if message == "__ping__" || message == "p" {
-                        // Silent ping - don't log or add to chat
-                    } else {
-                        console_log!("💬 Sent chat message: {}", message);
-                        // Add to pending messages only for real chat messages
-                        Self::add_pending_message(message, &self.pending_messages);
-                    }
+        if changed {
+            motion.interpolation.sync(&state.borrow().players, now);
+        }
+        changed
+    }
+
+    // Players a message is about to remove, so they can fade out where they were
+    fn departing_players(state: &GameState, message: &WebSocketMessage) -> Vec<Player> {
+        match message {
+            WebSocketMessage::PlayerLeft { username } => state.players.get(username).cloned().into_iter().collect(),
+            WebSocketMessage::GameState { players, .. } => state.players.values()
+                .filter(|known| !players.iter().any(|player| player.username == known.username))
+                .cloned()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Removes a player from the map with a fade-out and tells the host page
+    fn despawn_player(state: &Rc<RefCell<GameState>>, motion: &Rc<RefCell<Motion>>, events: &EventBus, username: &str, now: f64) -> bool {
+        let message = WebSocketMessage::PlayerLeft { username: username.to_string() };
+        if !Self::apply_message(state, motion, &message, now) {
+            return false;
+        }
+        events.emit(&ClientEvent::PlayerLeft { username: username.to_string() });
+        true
+    }
+
+    // Server position for our unit: replays unacknowledged moves on top and
+    // reports where we now expect to be
+    fn reconcile_position(ctx: &SocketContext, x: u32, y: u32, seq: Option<u64>, now: f64) {
+        let (predicted, confirmed) = {
+            let prediction = &mut ctx.motion.borrow_mut().prediction;
+            prediction.reconcile(x, y, seq, now);
+            (prediction.predicted(), prediction.pending() == 0)
+        };
+        if let Some((x, y)) = predicted {
+            ctx.events.emit(&ClientEvent::Position { x, y, confirmed });
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn setup_click_handler(&self) -> Result<(), JsValue> {
+        if let Some(ref canvas) = *self.canvas.borrow() {
+            let mut listeners = self.canvas_listeners.borrow_mut();
+            let sender = self.move_sender();
+            let camera = self.camera.clone();
+            let state = self.state.clone();
+            let minimap = self.minimap.clone();
+            let suppress_click = self.suppress_click.clone();
+            let canvas_for_click = canvas.clone();
+            listeners.attach(canvas, "click", move |event: Event| {
+                // The press was a camera drag, not a move order
+                if suppress_click.replace(false) {
+                    return;
                 }
-                Err(e) => {
-                    console_log!("❌ Failed to send message: {:?}", e);
-                    // Only show error for non-ping messages
-                    if message != "__ping__" && message != "p" {
-                        Self::append_chat_message("❌ Failed to send message - connection lost");
-                    }
+
+                let event: MouseEvent = event.unchecked_into();
+                let (canvas_x, canvas_y) = Self::canvas_point(&canvas_for_click, event.client_x() as f64, event.client_y() as f64);
+                // On the minimap a click looks there instead
+                if Self::jump_camera(&camera, &minimap, canvas_x, canvas_y) {
+                    return;
                 }
-            }
+                Self::move_to_point(&sender, &camera, &state, canvas_x, canvas_y);
+            })?;
+
+            // Right-click - same context action as a long-press, or a move order on the minimap
+            let sender = self.move_sender();
+            let camera = self.camera.clone();
+            let state = self.state.clone();
+            let minimap = self.minimap.clone();
+            let events = self.events.clone();
+            let canvas_for_menu = canvas.clone();
+            listeners.attach(canvas, "contextmenu", move |event: Event| {
+                event.prevent_default();
+                let event: MouseEvent = event.unchecked_into();
+                let (canvas_x, canvas_y) = Self::canvas_point(&canvas_for_menu, event.client_x() as f64, event.client_y() as f64);
+                // Camera released before the move, whose listeners may move it
+                let minimap_cell = minimap.get().screen_to_cell(&camera.borrow(), canvas_x, canvas_y);
+                if let Some((x, y)) = minimap_cell {
+                    Self::move_to_cell(&sender, &state, x, y);
+                    return;
+                }
+                Self::context_action(&camera, &state, &events, canvas_x, canvas_y);
+            })?;
+            
+            drop(listeners);
+            // Taps issue move orders too, so pick up the current player here as well
+            self.setup_touch_controls()?;
+
+            console_log!("🖱️ Click handler setup complete! Click to move around the grid.");
         }
         Ok(())
     }
 
+    // Keymap-driven step movement, camera centering and chat hotkeys
     #[wasm_bindgen]
-    pub fn start_game_loop(&mut self) -> Result<(), JsValue> {
-        let game_loop = Closure::wrap(Box::new(move |_timestamp: f64| {
-            // Call render on global gameClient at 60fps
-            let window = web_sys::window().unwrap();
-            if let Ok(game_client) = js_sys::Reflect::get(&window, &"gameClient".into()) {
-                if let Ok(render_fn) = js_sys::Reflect::get(&game_client, &"render_game".into()) {
-                    if let Ok(func) = render_fn.dyn_into::<js_sys::Function>() {
-                        let _ = func.call0(&game_client);
-                    }
+    pub fn setup_keyboard_controls(&self) -> Result<(), JsValue> {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let keymap = self.keymap.clone();
+        let sender = self.move_sender();
+        let state = self.state.clone();
+        let camera = self.camera.clone();
+        let events = self.events.clone();
+        let mut last_step = f64::NEG_INFINITY;
+        self.keyboard_listeners.borrow_mut().attach(&document, "keydown", move |event: Event| {
+            let event: KeyboardEvent = event.unchecked_into();
+            if event.ctrl_key() || event.meta_key() || event.alt_key() {
+                return;
+            }
+            let Some(action) = keymap.borrow().action_for(&event.code()) else {
+                return;
+            };
+
+            // Keys typed into the chat box or a form field are text, not commands
+            if Self::is_typing() {
+                if action == Action::LeaveChat {
+                    event.prevent_default();
+                    events.emit(&ClientEvent::Hotkey { action });
                 }
+                return;
             }
-            
-            // Schedule next frame
-            if let Ok(raf) = js_sys::Reflect::get(&window, &"requestAnimationFrame".into()) {
-                if let Ok(func) = raf.dyn_into::<js_sys::Function>() {
-                    let callback = js_sys::Reflect::get(&window, &"gameLoopCallback".into()).unwrap();
-                    let _ = func.call1(&window, &callback);
+            event.prevent_default();
+
+            if let Some((dx, dy)) = action.step() {
+                let now = js_sys::Date::now();
+                if now - last_step >= KEY_STEP_INTERVAL_MS {
+                    last_step = now;
+                    Self::step(&sender, &state, dx, dy);
                 }
+                return;
             }
-        }) as Box<dyn FnMut(f64)>);
-        
-        // Store callback globally for requestAnimationFrame
-        let window = web_sys::window().unwrap();
-        js_sys::Reflect::set(&window, &"gameLoopCallback".into(), game_loop.as_ref().unchecked_ref())?;
-        game_loop.forget();
-        
-        // Start the loop
-        if let Ok(raf) = js_sys::Reflect::get(&window, &"requestAnimationFrame".into()) {
-            if let Ok(func) = raf.dyn_into::<js_sys::Function>() {
-                let callback = js_sys::Reflect::get(&window, &"gameLoopCallback".into()).unwrap();
-                let _id = func.call1(&window, &callback)?;
-                // Store the ID if needed for cancellation
+            if action == Action::CenterCamera {
+                // sync_camera re-centres on our unit from the next frame on
+                camera.borrow_mut().follow = true;
             }
-        }
-        
-        console_log!("🎮 60fps game loop started!");
+            events.emit(&ClientEvent::Hotkey { action });
+        })?;
+
+        console_log!("⌨️ Keyboard controls ready");
         Ok(())
     }
 
+    // Current bindings as { action: [key codes] }
     #[wasm_bindgen]
-    pub fn render_game(&self) -> Result<(), JsValue> {
-        if let (Some(context), Some(_canvas)) = (&self.context, &self.canvas) {
-            // Clear canvas
-            context.clear_rect(0.0, 0.0, CANVAS_SIZE as f64, CANVAS_SIZE as f64);
-            
-            // Draw grid
-            self.draw_grid(context)?;
-            
-            // Draw players
-            self.draw_players(context)?;
+    pub fn get_keymap(&self) -> Result<JsValue, JsValue> {
+        self.keymap.borrow()
+            .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+            .map_err(JsValue::from)
+    }
+
+    // Binds a `KeyboardEvent.code` ("KeyW", "ArrowUp", ...) to an action such as
+    // "move_up", taking the key away from any other action
+    #[wasm_bindgen]
+    pub fn bind_key(&self, action: &str, code: &str) -> Result<(), JsValue> {
+        let action = Action::from_name(action)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown action '{}'", action)))?;
+        self.keymap.borrow_mut().bind(action, code);
+        Self::save_keymap(&self.keymap.borrow());
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn unbind_key(&self, code: &str) {
+        self.keymap.borrow_mut().unbind(code);
+        Self::save_keymap(&self.keymap.borrow());
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_keymap(&self) {
+        *self.keymap.borrow_mut() = Keymap::default();
+        Self::save_keymap(&self.keymap.borrow());
+    }
+
+    // One cell from where we expect to be, so quick presses chain up
+    fn step(sender: &MoveSender, state: &Rc<RefCell<GameState>>, dx: i32, dy: i32) {
+        let Some((x, y)) = Self::own_cell(&sender.motion.borrow(), &state.borrow()) else {
+            return;
+        };
+        let (Some(x), Some(y)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) else {
+            return;
+        };
+
+        if !state.borrow().terrain.is_walkable(x, y) {
+            return; // off the map, rock or water
         }
+        sender.send(x, y, None);
+    }
+
+    // Where we expect our unit to be: the newest predicted move, else the server's position
+    fn own_cell(motion: &Motion, state: &GameState) -> Option<(u32, u32)> {
+        motion.prediction.predicted()
+            .or_else(|| state.players.get(&state.username).map(|me| (me.x, me.y)))
+    }
+
+    fn is_typing() -> bool {
+        let Some(element) = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.active_element()) else {
+            return false;
+        };
+        matches!(element.tag_name().as_str(), "INPUT" | "TEXTAREA" | "SELECT")
+            || element.dyn_ref::<HtmlElement>().is_some_and(|element| element.is_content_editable())
+    }
+
+    fn load_keymap() -> Keymap {
+        let saved = web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .and_then(|storage| storage.get_item(KEYMAP_STORAGE_KEY).ok().flatten());
+
+        match saved {
+            Some(json) => Keymap::from_json(&json).unwrap_or_else(|e| {
+                console_log!("⚠️ {}; using default keys", e);
+                Keymap::default()
+            }),
+            None => Keymap::default(),
+        }
+    }
+
+    fn save_keymap(keymap: &Keymap) {
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        if let Some(storage) = storage {
+            if storage.set_item(KEYMAP_STORAGE_KEY, &keymap.to_json()).is_err() {
+                console_log!("⚠️ Could not save keymap");
+            }
+        }
+    }
+
+    fn setup_camera_controls(&self) -> Result<(), JsValue> {
+        let canvas = self.canvas.borrow().clone();
+        let Some(ref canvas) = canvas else {
+            return Ok(());
+        };
+        let mut listeners = self.canvas_listeners.borrow_mut();
+        let drag = Rc::new(RefCell::new(DragState::default()));
+
+        // Wheel - zoom around the cursor
+        let camera = self.camera.clone();
+        listeners.attach(canvas, "wheel", move |event: Event| {
+            let event: WheelEvent = event.unchecked_into();
+            event.prevent_default();
+            let canvas = event.target().unwrap().dyn_into::<HtmlCanvasElement>().unwrap();
+            let (canvas_x, canvas_y) = Self::canvas_point(&canvas, event.client_x() as f64, event.client_y() as f64);
+            let factor = (-event.delta_y() * 0.0015).exp();
+            camera.borrow_mut().zoom_at(factor, canvas_x, canvas_y);
+        })?;
+
+        // Mouse drag - pan
+        let drag_for_down = drag.clone();
+        let suppress_click = self.suppress_click.clone();
+        let camera = self.camera.clone();
+        let minimap = self.minimap.clone();
+        listeners.attach(canvas, "mousedown", move |event: Event| {
+            let event: MouseEvent = event.unchecked_into();
+            suppress_click.set(false);
+            let canvas = event.target().unwrap().dyn_into::<HtmlCanvasElement>().unwrap();
+            let (canvas_x, canvas_y) = Self::canvas_point(&canvas, event.client_x() as f64, event.client_y() as f64);
+            // Presses on the minimap are clicks there, never drags of the map
+            let on_minimap = minimap.get().screen_to_world(&camera.borrow(), canvas_x, canvas_y).is_some();
+            *drag_for_down.borrow_mut() = DragState { active: !on_minimap, last_x: canvas_x, last_y: canvas_y, ..DragState::default() };
+        })?;
+
+        // Mouse move - pan while dragging, and track the hovered cell for the route preview and tooltip
+        let drag_for_move = drag.clone();
+        let camera = self.camera.clone();
+        let hover = self.hover.clone();
+        let minimap = self.minimap.clone();
+        let suppress_click = self.suppress_click.clone();
+        listeners.attach(canvas, "mousemove", move |event: Event| {
+            let event: MouseEvent = event.unchecked_into();
+            let canvas = event.target().unwrap().dyn_into::<HtmlCanvasElement>().unwrap();
+            let (canvas_x, canvas_y) = Self::canvas_point(&canvas, event.client_x() as f64, event.client_y() as f64);
+            Self::drag_to(&drag_for_move, &camera, &suppress_click, canvas_x, canvas_y);
+            let camera = camera.borrow();
+            // The map under the minimap is hidden, so there is nothing to preview there
+            let cell = match minimap.get().screen_to_world(&camera, canvas_x, canvas_y) {
+                Some(_) => None,
+                None => camera.screen_to_cell(canvas_x, canvas_y),
+            };
+            hover.borrow_mut().point_at(cell, js_sys::Date::now());
+        })?;
+
+        let drag_for_up = drag.clone();
+        listeners.attach(canvas, "mouseup", move |_event: Event| {
+            drag_for_up.borrow_mut().active = false;
+        })?;
+
+        let hover = self.hover.clone();
+        listeners.attach(canvas, "mouseleave", move |_event: Event| {
+            drag.borrow_mut().active = false;
+            hover.borrow_mut().point_at(None, js_sys::Date::now());
+        })?;
+
         Ok(())
     }
 
-    fn draw_grid(&self, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
-        context.set_stroke_style_str("#333");
-        context.set_line_width(0.5);
-        
-        for i in 0..=GRID_SIZE {
-            let pos = (i * CELL_SIZE) as f64;
-            context.begin_path();
-            context.move_to(pos, 0.0);
-            context.line_to(pos, CANVAS_SIZE as f64);
-            context.stroke();
-            
-            context.begin_path();
-            context.move_to(0.0, pos);
-            context.line_to(CANVAS_SIZE as f64, pos);
-            context.stroke();
+    // Touch - tap to move, long-press for the context action, one or two
+    // fingers to pan and pinch to zoom
+    fn setup_touch_controls(&self) -> Result<(), JsValue> {
+        let canvas = self.canvas.borrow().clone();
+        let Some(ref canvas) = canvas else {
+            return Ok(());
+        };
+        let tracker = Rc::new(RefCell::new(GestureTracker::default()));
+
+        let camera = self.camera.clone();
+        let state = self.state.clone();
+        let minimap = self.minimap.clone();
+        let events = self.events.clone();
+        let sender = self.move_sender();
+        let apply: Rc<dyn Fn(Gesture)> = Rc::new(move |gesture| match gesture {
+            Gesture::Pan { dx, dy } => camera.borrow_mut().pan_by(dx, dy),
+            Gesture::Pinch { factor, x, y } => camera.borrow_mut().zoom_at(factor, x, y),
+            // On the minimap, tap looks there and long-press moves there
+            Gesture::Tap { x, y } => {
+                if !Self::jump_camera(&camera, &minimap, x, y) {
+                    Self::move_to_point(&sender, &camera, &state, x, y);
+                }
+            }
+            Gesture::LongPress { x, y } => {
+                let minimap_cell = minimap.get().screen_to_cell(&camera.borrow(), x, y);
+                match minimap_cell {
+                    Some((cell_x, cell_y)) => Self::move_to_cell(&sender, &state, cell_x, cell_y),
+                    None => Self::context_action(&camera, &state, &events, x, y),
+                }
+            }
+        });
+
+        let canvas_for_touch = canvas.clone();
+        let on_touch = Rc::new(move |event: TouchEvent| {
+            // Also stops the browser from synthesizing mouse events and a click
+            event.prevent_default();
+
+            let phase = match event.type_().as_str() {
+                "touchstart" => TouchPhase::Start,
+                "touchmove" => TouchPhase::Move,
+                "touchend" => TouchPhase::End,
+                _ => TouchPhase::Cancel,
+            };
+            let touches = event.touches();
+            let points: Vec<(f64, f64)> = (0..touches.length().min(2))
+                .filter_map(|i| touches.get(i))
+                .map(|touch| Self::canvas_point(&canvas_for_touch, touch.client_x() as f64, touch.client_y() as f64))
+                .collect();
+
+            let gestures = tracker.borrow_mut().touch(phase, &points, js_sys::Date::now());
+            for gesture in gestures {
+                apply(gesture);
+            }
+
+            if phase == TouchPhase::Start && points.len() == 1 {
+                Self::schedule_long_press(&tracker, &apply);
+            }
+        });
+
+        let mut listeners = self.canvas_listeners.borrow_mut();
+        for event_name in ["touchstart", "touchmove", "touchend", "touchcancel"] {
+            let on_touch = on_touch.clone();
+            listeners.attach(canvas, event_name, move |event: Event| on_touch(event.unchecked_into()))?;
         }
+
         Ok(())
     }
 
-    fn draw_players(&self, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
-        for player in self.players.values() {
-            let x = (player.x * CELL_SIZE) as f64;
-            let y = (player.y * CELL_SIZE) as f64;
-            
-            if player.username == self.username {
-                // Draw self in green
-                context.set_fill_style_str("#4CAF50");
-            } else {
-                // Draw others in red
-                context.set_fill_style_str("#F44336");
+    fn schedule_long_press(tracker: &Rc<RefCell<GestureTracker>>, apply: &Rc<dyn Fn(Gesture)>) {
+        let tracker = tracker.clone();
+        let apply = apply.clone();
+        let long_press_callback = Closure::once_into_js(move || {
+            // Does nothing if the finger moved or lifted in the meantime
+            let gesture = tracker.borrow_mut().poll_long_press(js_sys::Date::now());
+            if let Some(gesture) = gesture {
+                apply(gesture);
             }
-            
-            context.fill_rect(x + 2.0, y + 2.0, (CELL_SIZE - 4) as f64, (CELL_SIZE - 4) as f64);
-            
-            // Draw username
-            context.set_fill_style_str("white");
-            context.set_font("10px Arial");
-            context.fill_text(&player.username, x + 2.0, y + CELL_SIZE as f64 - 2.0)?;
+        });
+
+        let _ = web_sys::window().unwrap().set_timeout_with_callback_and_timeout_and_arguments_0(
+            long_press_callback.as_ref().unchecked_ref(),
+            LONG_PRESS_MS as i32,
+        );
+    }
+
+    // Move order for the cell under a canvas point, shared by clicks and taps
+    fn move_to_point(sender: &MoveSender, camera: &Rc<RefCell<Camera>>, state: &Rc<RefCell<GameState>>, canvas_x: f64, canvas_y: f64) {
+        let Some((x, y)) = camera.borrow().screen_to_cell(canvas_x, canvas_y) else {
+            return;
+        };
+        console_log!("🎯 Click at grid position: ({}, {})", x, y);
+        Self::move_to_cell(sender, state, x, y);
+    }
+
+    // Move order along a planned route, unless the cell can't be reached
+    fn move_to_cell(sender: &MoveSender, state: &Rc<RefCell<GameState>>, x: u32, y: u32) {
+        // Rock and water would only be rejected by the server
+        if !state.borrow().terrain.is_walkable(x, y) {
+            console_log!("🚫 ({}, {}) is not walkable", x, y);
+            return;
         }
+
+        // Send the route previewed on hover; without a unit yet, just the target
+        let from = Self::own_cell(&sender.motion.borrow(), &state.borrow());
+        let planned = from.map(|from| pathfinding::plan(&state.borrow(), from, (x, y)));
+        match planned {
+            Some(Some(path)) if path.is_empty() => {} // already there
+            Some(Some(path)) => {
+                sender.send(x, y, Some(path));
+            }
+            Some(None) => {
+                // Our view of the map may be stale; let the server decide
+                console_log!("🚫 No route to ({}, {}), sending the target alone", x, y);
+                if sender.send_unplanned(x, y) {
+                    sender.events.emit(&ClientEvent::NoRoute { x, y });
+                }
+            }
+            None => {
+                sender.send(x, y, None);
+            }
+        }
+    }
+
+    // Centres the camera on the world point under a minimap click. Returns
+    // false when the point is not on the minimap.
+    fn jump_camera(camera: &Rc<RefCell<Camera>>, minimap: &Rc<Cell<Minimap>>, canvas_x: f64, canvas_y: f64) -> bool {
+        let mut camera = camera.borrow_mut();
+        let Some((world_x, world_y)) = minimap.get().screen_to_world(&camera, canvas_x, canvas_y) else {
+            return false;
+        };
+        camera.follow = false;
+        camera.center_on(world_x, world_y);
+        true
+    }
+
+    // Right-click or long-press: lets the host page offer actions for a cell
+    fn context_action(camera: &Rc<RefCell<Camera>>, state: &Rc<RefCell<GameState>>, events: &EventBus, canvas_x: f64, canvas_y: f64) {
+        let Some((x, y)) = camera.borrow().screen_to_cell(canvas_x, canvas_y) else {
+            return;
+        };
+        let player = state.borrow().players.values()
+            .find(|player| player.x == x && player.y == y)
+            .map(|player| player.username.clone());
+        events.emit(&ClientEvent::ContextAction { x, y, player });
+    }
+
+    fn drag_to(drag: &Rc<RefCell<DragState>>, camera: &Rc<RefCell<Camera>>, suppress_click: &Rc<Cell<bool>>, x: f64, y: f64) {
+        let mut drag = drag.borrow_mut();
+        if !drag.active {
+            return;
+        }
+
+        let (delta_x, delta_y) = (x - drag.last_x, y - drag.last_y);
+        drag.last_x = x;
+        drag.last_y = y;
+        drag.distance += delta_x.hypot(delta_y);
+
+        // Small jitter while clicking should neither pan nor cancel the click
+        if drag.distance > DRAG_THRESHOLD_PX {
+            suppress_click.set(true);
+            camera.borrow_mut().pan_by(delta_x, delta_y);
+        }
+    }
+
+    // Client (CSS) coordinates to canvas pixels, accounting for CSS scaling
+    fn canvas_point(canvas: &HtmlCanvasElement, client_x: f64, client_y: f64) -> (f64, f64) {
+        let rect = canvas.get_bounding_client_rect();
+        let scale_x = if rect.width() > 0.0 { canvas.width() as f64 / rect.width() } else { 1.0 };
+        let scale_y = if rect.height() > 0.0 { canvas.height() as f64 / rect.height() } else { 1.0 };
+        ((client_x - rect.x()) * scale_x, (client_y - rect.y()) * scale_y)
+    }
+
+    #[wasm_bindgen]
+    pub fn send_move_command(&self, x: u32, y: u32) -> Result<(), JsValue> {
+        if !self.state.borrow().map.contains(x, y) {
+            return Err(JsValue::from_str("Position is outside the map"));
+        }
+
+        self.move_sender().send(x, y, None);
         Ok(())
     }
 
+    fn move_sender(&self) -> MoveSender {
+        MoveSender {
+            username: self.username.borrow().clone(),
+            room: self.room.borrow().clone(),
+            mode: self.mode.clone(),
+            websocket: self.websocket.clone(),
+            outbox: self.outbox.clone(),
+            connection_state: self.connection_state.clone(),
+            pending_messages: self.pending_messages.clone(),
+            motion: self.motion.clone(),
+            events: self.events.clone(),
+        }
+    }
+
     #[wasm_bindgen]
-    pub fn update_player(&mut self, username: &str, x: u32, y: u32, health: u32, resources: u32) {
-        let player = Player {
-            username: username.to_string(),
-            x, y, health, resources,
-            room: self.room.clone(),
+    pub fn send_message(&self, message: &str) -> Result<(), JsValue> {
+        let nonce = self.next_nonce();
+        
+        // Format like server messages
+        let timestamp = Self::format_current_timestamp(&self.clock);
+        self.pending_messages.borrow_mut().insert(&nonce, &self.username.borrow(), message, &timestamp);
+        
+        let chat_message = WebSocketMessage::Message {
+            username: self.username.borrow().clone(),
+            message: message.to_string(),
+            room: self.room.borrow().clone(),
+            nonce: Some(nonce.clone()),
         };
         
-        if username == self.username {
-            self.my_player = Some(player.clone());
+        match Self::send_or_queue(chat_message, &self.websocket, &self.outbox, &self.connection_state, &self.pending_messages, &self.events) {
+            Delivery::Sent => console_log!("💬 Sent chat message: {}", message),
+            Delivery::Queued => console_log!("📥 Chat message queued until reconnected: {}", message),
+            Delivery::Dropped => {
+                console_log!("❌ WebSocket not connected, cannot send message");
+                self.pending_messages.borrow_mut().remove(&nonce);
+                self.events.emit(&ClientEvent::Error { message: "Not connected to server".to_string() });
+                return Ok(());
+            }
         }
         
-        self.players.insert(username.to_string(), player);
+        self.events.emit(&ClientEvent::Chat {
+            username: self.username.borrow().clone(),
+            message: message.to_string(),
+            timestamp,
+            status: ChatStatus::Pending,
+            nonce: Some(nonce),
+        });
+        Ok(())
     }
 
+    // Re-sends a chat line that was marked failed, keeping its nonce
     #[wasm_bindgen]
-    pub fn update_all_players(&mut self, players_json: &str) -> Result<(), JsValue> {
-        if let Ok(players) = serde_json::from_str::<Vec<Player>>(players_json) {
-            self.players.clear();
-            
-            for player in players {
-                if player.username == self.username {
-                    self.my_player = Some(player.clone());
-                }
-                self.players.insert(player.username.clone(), player);
+    pub fn retry_message(&self, nonce: &str) -> Result<(), JsValue> {
+        let Some(pending) = self.pending_messages.borrow_mut().retry(nonce) else {
+            return Err(JsValue::from_str("No failed message with that id"));
+        };
+        
+        let chat_message = WebSocketMessage::Message {
+            username: self.username.borrow().clone(),
+            message: pending.message.clone(),
+            room: self.room.borrow().clone(),
+            nonce: Some(nonce.to_string()),
+        };
+        
+        match Self::send_or_queue(chat_message, &self.websocket, &self.outbox, &self.connection_state, &self.pending_messages, &self.events) {
+            Delivery::Sent => console_log!("🔁 Retrying chat message: {}", pending.message),
+            Delivery::Queued => console_log!("📥 Retry queued until reconnected: {}", pending.message),
+            Delivery::Dropped => {
+                self.pending_messages.borrow_mut().fail(nonce);
+                self.events.emit(&ClientEvent::Error { message: "Not connected to server".to_string() });
+                return Ok(());
             }
         }
+        
+        self.events.emit(&ClientEvent::Chat {
+            username: self.username.borrow().clone(),
+            message: pending.message,
+            timestamp: pending.timestamp,
+            status: ChatStatus::Pending,
+            nonce: Some(nonce.to_string()),
+        });
         Ok(())
     }
 
-    // Static helper functions for UI updates
-    fn get_server_url() -> String {
-        let window = web_sys::window().unwrap();
-        let location = window.location();
-        
-        if let Ok(hostname) = location.hostname() {
-            console_log!("🌐 Detected hostname: {}", hostname);
-            if hostname.contains("localhost") || hostname.contains("127.0.0.1") {
-                // Check if local server is available, otherwise use production
-                console_log!("🏠 Local development detected, but using production server for reliability");
-                "wss://ironvein-server-production.up.railway.app".to_string()
-            } else {
-                console_log!("🌍 Production environment detected");
-                "wss://ironvein-server-production.up.railway.app".to_string()
+    // Sends right away when the socket is open, otherwise holds the message
+    // while a connect or reconnect is in progress
+    fn send_or_queue(
+        message: WebSocketMessage,
+        websocket: &Rc<RefCell<Option<WebSocket>>>,
+        outbox: &Rc<RefCell<Outbox>>,
+        connection_state: &Rc<Cell<ConnectionState>>,
+        pending_messages: &Rc<RefCell<PendingChats>>,
+        events: &EventBus,
+    ) -> Delivery {
+        if Self::is_socket_open(websocket) {
+            // Cloned out so the send's listeners may disconnect or reconnect
+            let open = websocket.borrow().clone();
+            if let Some(ref websocket) = open {
+                match Self::transmit(websocket, &message) {
+                    Ok(()) => {
+                        Self::handle_sent(&message, pending_messages, events);
+                        return Delivery::Sent;
+                    }
+                    Err(e) => console_log!("❌ Failed to send message: {:?}", e),
+                }
             }
-        } else {
-            console_log!("🌍 Fallback to production server");
-            "wss://ironvein-server-production.up.railway.app".to_string()
         }
+
+        if connection_state.get() == ConnectionState::Disconnected {
+            return Delivery::Dropped;
+        }
+
+        let discarded = outbox.borrow_mut().push(message, js_sys::Date::now());
+        Self::report_discarded(discarded, pending_messages, events);
+        Delivery::Queued
     }
 
-    fn update_player_list(username: &str, x: u32, y: u32, health: u32, resources: u32) {
-        let window = web_sys::window().unwrap();
-        if let Ok(update_fn) = js_sys::Reflect::get(&window, &"updatePlayerInList".into()) {
-            if let Ok(func) = update_fn.dyn_into::<js_sys::Function>() {
-                let args = js_sys::Array::new();
-                args.push(&username.into());
-                args.push(&(x as f64).into());
-                args.push(&(y as f64).into());
-                args.push(&(health as f64).into());
-                args.push(&(resources as f64).into());
-                let _ = func.apply(&window, &args);
+    fn transmit(websocket: &WebSocket, message: &WebSocketMessage) -> Result<(), JsValue> {
+        let message_json = serde_json::to_string(message)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))?;
+        websocket.send_with_str(&message_json)
+    }
+
+    // Follow-up once a message actually left: optimistic moves and chat ack timeouts
+    fn handle_sent(message: &WebSocketMessage, pending_messages: &Rc<RefCell<PendingChats>>, events: &EventBus) {
+        match message {
+            WebSocketMessage::Move { x, y, path, .. } => {
+                console_log!("📤 Sent move command: ({}, {})", x, y);
+                // Optimistic update, as far as the move is predicted
+                let (x, y) = path.as_ref()
+                    .and_then(|path| path.first().copied())
+                    .unwrap_or((*x, *y));
+                events.emit(&ClientEvent::Position { x, y, confirmed: false });
+            }
+            WebSocketMessage::Message { username, nonce: Some(nonce), .. } => {
+                pending_messages.borrow_mut().mark_sent(nonce, js_sys::Date::now());
+                Self::schedule_chat_timeout(pending_messages, events, username);
             }
+            _ => {}
         }
     }
 
-    fn remove_player_from_list(username: &str) {
-        let window = web_sys::window().unwrap();
-        if let Ok(remove_fn) = js_sys::Reflect::get(&window, &"removePlayerFromList".into()) {
-            if let Ok(func) = remove_fn.dyn_into::<js_sys::Function>() {
-                let args = js_sys::Array::new();
-                args.push(&username.into());
-                let _ = func.apply(&window, &args);
+    fn report_discarded(discarded: Vec<Discarded>, pending_messages: &Rc<RefCell<PendingChats>>, events: &EventBus) {
+        for item in discarded {
+            let nonce = item.nonce().map(str::to_string);
+            console_log!("🗑️ Discarded queued {} ({:?})", item.message_type(), item.reason);
+
+            // Chat lines become failed so the player can retry them
+            if let (Some(nonce), WebSocketMessage::Message { username, .. }) = (&nonce, &item.message) {
+                // Released before emitting, so listeners can retry or send right away
+                let failed = pending_messages.borrow_mut().fail(nonce);
+                if let Some(pending) = failed {
+                    events.emit(&ClientEvent::Chat {
+                        username: username.clone(),
+                        message: pending.message,
+                        timestamp: pending.timestamp,
+                        status: ChatStatus::Failed,
+                        nonce: Some(nonce.clone()),
+                    });
+                }
             }
+
+            events.emit(&ClientEvent::OutboundDiscarded {
+                message_type: item.message_type().to_string(),
+                reason: item.reason,
+                nonce,
+            });
         }
     }
 
-    fn update_game_client_player(username: &str, x: u32, y: u32, health: u32, resources: u32) {
+    fn next_nonce(&self) -> String {
+        let counter = self.next_nonce.get();
+        self.next_nonce.set(counter + 1);
+        // Random part so clients that start in the same millisecond differ
+        let salt = (js_sys::Math::random() * u32::MAX as f64) as u32;
+        format!("{:x}-{:x}-{:x}", js_sys::Date::now() as u64, counter, salt)
+    }
+
+    fn schedule_chat_timeout(pending_messages: &Rc<RefCell<PendingChats>>, events: &EventBus, username: &str) {
+        let pending_messages = pending_messages.clone();
+        let events = events.clone();
+        let username = username.to_string();
+        let timeout_callback = Closure::once_into_js(move || {
+            let failed = pending_messages.borrow_mut().expire(js_sys::Date::now());
+            for (nonce, pending) in failed {
+                console_log!("⚠️ Chat message not acknowledged: {}", pending.message);
+                events.emit(&ClientEvent::Chat {
+                    username: username.clone(),
+                    message: pending.message,
+                    timestamp: pending.timestamp,
+                    status: ChatStatus::Failed,
+                    nonce: Some(nonce),
+                });
+            }
+        });
+        
         let window = web_sys::window().unwrap();
-        if let Ok(game_client) = js_sys::Reflect::get(&window, &"gameClient".into()) {
-            if let Ok(update_fn) = js_sys::Reflect::get(&game_client, &"update_player".into()) {
-                if let Ok(func) = update_fn.dyn_into::<js_sys::Function>() {
-                    let args = js_sys::Array::new();
-                    args.push(&username.into());
-                    args.push(&(x as f64).into());
-                    args.push(&(y as f64).into());
-                    args.push(&(health as f64).into());
-                    args.push(&(resources as f64).into());
-                    let _ = func.apply(&game_client, &args);
+        let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+            timeout_callback.unchecked_ref(),
+            CHAT_ACK_TIMEOUT_MS as i32 + 50,
+        );
+    }
+
+    // Starts the render loop; calling it again while running is a no-op
+    #[wasm_bindgen]
+    pub fn start_game_loop(&self) -> Result<(), JsValue> {
+        if self.game_loop.borrow().is_some() {
+            return Ok(());
+        }
+
+        let renderer = self.renderer.clone();
+        let state = self.state.clone();
+        let motion = self.motion.clone();
+        let hover = self.hover.clone();
+        let minimap = self.minimap.clone();
+        let camera = self.camera.clone();
+        let events = self.events.clone();
+        let game_loop = self.game_loop.clone();
+        let game_loop_id = self.game_loop_id.clone();
+        let loop_paused = self.loop_paused.clone();
+        let frame = Closure::wrap(Box::new(move |_timestamp: f64| {
+            game_loop_id.set(None);
+
+            let now = js_sys::Date::now();
+            let has_canvas = renderer.borrow().is_some();
+            if has_canvas {
+                motion.borrow_mut().prediction.expire(now);
+                motion.borrow_mut().status.prune(now);
+
+                // Players nothing was heard of for too long are treated as gone.
+                // Done before borrowing the renderer, as player_left listeners
+                // may call back into the client.
+                let stale = {
+                    let state = state.borrow();
+                    motion.borrow_mut().lifecycle.expire(&state.players, &state.username, now)
+                };
+                for username in stale {
+                    console_log!("⌛ Player {} went silent, removing", username);
+                    Self::despawn_player(&state, &motion, &events, &username, now);
+                }
+            }
+
+            // Render shared state at up to 60fps; unchanged frames are skipped
+            if let Some(ref mut renderer) = *renderer.borrow_mut() {
+                Self::sync_camera(&camera, &state.borrow(), &motion.borrow(), now);
+
+                // Replan the route preview as the pointer, our unit or the map change
+                let from = Self::own_cell(&motion.borrow(), &state.borrow());
+                if hover.borrow_mut().refresh(from, &state.borrow()) {
+                    renderer.request_redraw();
                 }
+                let _ = renderer.render(&state.borrow(), &camera.borrow(), &motion.borrow(), &hover.borrow(), &minimap.get(), now);
             }
+            
+            // Schedule next frame
+            Self::schedule_frame(&game_loop, &game_loop_id, &loop_paused);
+        }) as Box<dyn FnMut(f64)>);
+        *self.game_loop.borrow_mut() = Some(frame);
+
+        self.setup_visibility_handler()?;
+        Self::schedule_frame(&self.game_loop, &self.game_loop_id, &self.loop_paused);
+        
+        console_log!("🎮 60fps game loop started!");
+        Ok(())
+    }
+
+    // Stops the render loop and releases its callback
+    #[wasm_bindgen]
+    pub fn stop_game_loop(&self) {
+        Self::cancel_frame(&self.game_loop_id);
+        if self.game_loop.borrow_mut().take().is_some() {
+            console_log!("⏹️ Game loop stopped");
         }
+        self.document_listeners.borrow_mut().detach_all();
     }
 
-    fn update_all_game_players(players: &[Player]) {
-        let window = web_sys::window().unwrap();
-        if let Ok(game_client) = js_sys::Reflect::get(&window, &"gameClient".into()) {
-            if let Ok(players_json) = serde_json::to_string(players) {
-                if let Ok(update_fn) = js_sys::Reflect::get(&game_client, &"update_all_players".into()) {
-                    if let Ok(func) = update_fn.dyn_into::<js_sys::Function>() {
-                        let args = js_sys::Array::new();
-                        args.push(&players_json.into());
-                        let _ = func.apply(&game_client, &args);
-                    }
-                }
+    #[wasm_bindgen]
+    pub fn pause_game_loop(&self) {
+        self.loop_paused.set(true);
+        Self::cancel_frame(&self.game_loop_id);
+    }
+
+    #[wasm_bindgen]
+    pub fn resume_game_loop(&self) {
+        self.loop_paused.set(false);
+        Self::request_redraw(&self.renderer);
+        Self::schedule_frame(&self.game_loop, &self.game_loop_id, &self.loop_paused);
+    }
+
+    // True while a frame is scheduled: started, not paused and the tab is visible
+    #[wasm_bindgen]
+    pub fn is_game_loop_running(&self) -> bool {
+        self.game_loop_id.get().is_some()
+    }
+
+    // Unmounts the client: stops the loop and removes every socket, canvas and page handler
+    #[wasm_bindgen]
+    pub fn destroy(&self) {
+        self.teardown();
+        Self::set_mode(&self.mode, &self.events, ClientMode::Disconnected);
+        Self::set_connection_state(&self.connection_state, &self.events, ConnectionState::Disconnected, 0, None);
+        self.outbox.borrow_mut().clear();
+        self.events.clear();
+        *self.renderer.borrow_mut() = None;
+        *self.canvas.borrow_mut() = None;
+        console_log!("🧹 IronVein client destroyed");
+    }
+
+    fn teardown(&self) {
+        self.stop_game_loop();
+        self.canvas_listeners.borrow_mut().detach_all();
+        self.keyboard_listeners.borrow_mut().detach_all();
+        self.close_socket();
+    }
+
+    fn setup_visibility_handler(&self) -> Result<(), JsValue> {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let renderer = self.renderer.clone();
+        let game_loop = self.game_loop.clone();
+        let game_loop_id = self.game_loop_id.clone();
+        let loop_paused = self.loop_paused.clone();
+        self.document_listeners.borrow_mut().attach(&document, "visibilitychange", move |_event: Event| {
+            if Self::page_hidden() {
+                console_log!("⏸️ Tab hidden, game loop paused");
+                Self::cancel_frame(&game_loop_id);
+            } else {
+                Self::request_redraw(&renderer);
+                Self::schedule_frame(&game_loop, &game_loop_id, &loop_paused);
+            }
+        })
+    }
+
+    fn schedule_frame(game_loop: &FrameCallback, game_loop_id: &Rc<Cell<Option<i32>>>, loop_paused: &Rc<Cell<bool>>) {
+        if game_loop_id.get().is_some() || loop_paused.get() || Self::page_hidden() {
+            return;
+        }
+
+        if let Some(ref frame) = *game_loop.borrow() {
+            match web_sys::window().unwrap().request_animation_frame(frame.as_ref().unchecked_ref()) {
+                Ok(id) => game_loop_id.set(Some(id)),
+                Err(e) => console_log!("❌ Failed to schedule frame: {:?}", e),
             }
         }
     }
 
-    fn update_position_display(username: &str, x: u32, y: u32) {
-        let window = web_sys::window().unwrap();
-        let document = window.document().unwrap();
-        
-        // Update position display if it's the current user
-        if let Some(user_display) = document.get_element_by_id("userDisplay") {
-            if let Some(current_user) = user_display.text_content() {
-                if current_user == username {
-                    if let Some(pos_display) = document.get_element_by_id("positionDisplay") {
-                        pos_display.set_text_content(Some(&format!("Position: ({}, {})", x, y)));
+    fn cancel_frame(game_loop_id: &Rc<Cell<Option<i32>>>) {
+        if let Some(id) = game_loop_id.take() {
+            let _ = web_sys::window().unwrap().cancel_animation_frame(id);
+        }
+    }
+
+    fn request_redraw(renderer: &Rc<RefCell<Option<Renderer>>>) {
+        if let Some(ref mut renderer) = *renderer.borrow_mut() {
+            renderer.request_redraw();
+        }
+    }
+
+    fn page_hidden() -> bool {
+        web_sys::window()
+            .and_then(|window| window.document())
+            .is_some_and(|document| document.hidden())
+    }
+
+    // Draws a frame now, even if nothing changed
+    #[wasm_bindgen]
+    pub fn render_game(&self) -> Result<(), JsValue> {
+        if let Some(ref mut renderer) = *self.renderer.borrow_mut() {
+            let now = js_sys::Date::now();
+            Self::sync_camera(&self.camera, &self.state.borrow(), &self.motion.borrow(), now);
+            renderer.request_redraw();
+            renderer.render(&self.state.borrow(), &self.camera.borrow(), &self.motion.borrow(), &self.hover.borrow(), &self.minimap.get(), now)?;
+        }
+        Ok(())
+    }
+
+    // Keeps the camera on the current map and, in follow mode, on our unit
+    fn sync_camera(camera: &Rc<RefCell<Camera>>, state: &GameState, motion: &Motion, now: f64) {
+        let mut camera = camera.borrow_mut();
+        camera.set_world(state.map.world_width(), state.map.world_height());
+        if !camera.follow {
+            return;
+        }
+        if let Some(me) = state.players.get(&state.username) {
+            // Track the drawn position so the unit doesn't wobble against the view
+            let (world_x, world_y) = match motion.position(&me.username, now) {
+                Some((x, y)) => ((x + 0.5) * grid::CELL_SIZE as f64, (y + 0.5) * grid::CELL_SIZE as f64),
+                None => grid::cell_center(me.x, me.y),
+            };
+            camera.center_on(world_x, world_y);
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn update_player(&self, username: &str, x: u32, y: u32, health: u32, resources: u32) {
+        let message = WebSocketMessage::PlayerUpdate {
+            username: username.to_string(),
+            x, y, health, resources,
+            seq: None,
+        };
+        Self::apply_message(&self.state, &self.motion, &message, js_sys::Date::now());
+    }
+
+    #[wasm_bindgen]
+    pub fn update_all_players(&self, players_json: &str) -> Result<(), JsValue> {
+        if let Ok(players) = serde_json::from_str::<Vec<Player>>(players_json) {
+            let message = WebSocketMessage::GameState { players, width: None, height: None };
+            Self::apply_message(&self.state, &self.motion, &message, js_sys::Date::now());
+        }
+        Ok(())
+    }
+
+    // Removes a player from the map (fading it out) and emits player_left
+    #[wasm_bindgen]
+    pub fn remove_player(&self, username: &str) -> bool {
+        Self::despawn_player(&self.state, &self.motion, &self.events, username, js_sys::Date::now())
+    }
+
+    // Players silent for this long are removed; 0 (the default) keeps them.
+    // Only useful when the server sends keepalives for idle players.
+    #[wasm_bindgen]
+    pub fn set_stale_player_timeout(&self, timeout_ms: f64) {
+        self.motion.borrow_mut().lifecycle.set_stale_after(timeout_ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn get_stale_player_timeout(&self) -> f64 {
+        self.motion.borrow().lifecycle.stale_after_ms()
+    }
+
+    // Shows or hides the minimap in the bottom-right corner of the canvas
+    #[wasm_bindgen]
+    pub fn set_minimap_visible(&self, visible: bool) {
+        self.minimap.set(Minimap { visible });
+    }
+
+    #[wasm_bindgen]
+    pub fn is_minimap_visible(&self) -> bool {
+        self.minimap.get().visible
+    }
+
+    // Loads unit sprites from an atlas image and its JSON frame metadata.
+    // Resolves once they are drawn; on failure units keep their current look.
+    #[wasm_bindgen]
+    pub fn load_sprite_atlas(&self, image_url: &str, metadata_url: &str) -> js_sys::Promise {
+        let sprites = self.sprites.clone();
+        let renderer = self.renderer.clone();
+        let (image_url, metadata_url) = (image_url.to_string(), metadata_url.to_string());
+        wasm_bindgen_futures::future_to_promise(async move {
+            match SpriteSheet::load(&image_url, &metadata_url).await {
+                Ok(sheet) => {
+                    *sprites.borrow_mut() = Some(sheet);
+                    if let Some(ref mut renderer) = *renderer.borrow_mut() {
+                        renderer.request_redraw();
                     }
+                    console_log!("🖼️ Sprite atlas loaded from {}", image_url);
+                    Ok(JsValue::TRUE)
+                }
+                Err(e) => {
+                    console_log!("❌ Failed to load sprite atlas: {:?}", e);
+                    Err(e)
                 }
             }
+        })
+    }
+
+    // Goes back to drawing units as plain squares
+    #[wasm_bindgen]
+    pub fn clear_sprite_atlas(&self) {
+        *self.sprites.borrow_mut() = None;
+        if let Some(ref mut renderer) = *self.renderer.borrow_mut() {
+            renderer.request_redraw();
         }
     }
 
-    fn handle_ping_response() {
+    // Static helper functions for UI updates
+    fn resolve_server_url(&self) -> String {
         let window = web_sys::window().unwrap();
-        if let Ok(callback) = js_sys::Reflect::get(&window, &"onPingReceived".into()) {
-            if let Ok(func) = callback.dyn_into::<js_sys::Function>() {
-                let _ = func.call0(&window);
-            }
+        let location = window.location();
+        let page_is_secure = location.protocol().map(|p| p == "https:").unwrap_or(true);
+
+        // 1. Explicitly configured via set_server_url
+        if let Some(ref server_url) = *self.server_url.borrow() {
+            return normalize_server_url(server_url, page_is_secure);
         }
-    }
 
-    fn handle_chat_message(chat_msg: ChatMessage, pending_messages: &Rc<RefCell<HashMap<String, web_sys::Element>>>) {
-        let formatted_timestamp = Self::format_timestamp(&chat_msg.timestamp);
-        let formatted_message = format!("[{}] {}: {}", formatted_timestamp, chat_msg.username, chat_msg.message);
-        
-        // Remove from pending if it's our message
-        let message_key = chat_msg.message.to_lowercase().trim().to_string();
-        let mut pending = pending_messages.borrow_mut();
-        if let Some(pending_element) = pending.remove(&message_key) {
-            if let Some(parent) = pending_element.parent_node() {
-                let _ = parent.remove_child(&pending_element);
+        // 2. ?server= query parameter
+        if let Some(server_url) = location.search().ok()
+            .and_then(|search| UrlSearchParams::new_with_str(&search).ok())
+            .and_then(|params| params.get("server"))
+            .filter(|server_url| !server_url.trim().is_empty())
+        {
+            console_log!("🔧 Using server from ?server= parameter: {}", server_url);
+            return normalize_server_url(&server_url, page_is_secure);
+        }
+
+        // 3. Build-time VITE_SERVER_URL
+        if let Some(server_url) = BUILD_SERVER_URL.filter(|url| !url.trim().is_empty()) {
+            return normalize_server_url(server_url, page_is_secure);
+        }
+
+        if let Ok(hostname) = location.hostname() {
+            console_log!("🌐 Detected hostname: {}", hostname);
+            if hostname.contains("localhost") || hostname.contains("127.0.0.1") {
+                console_log!("🏠 Local development detected, using production server (override with ?server=localhost:8080)");
+            } else {
+                console_log!("🌍 Production environment detected");
             }
+        } else {
+            console_log!("🌍 Fallback to production server");
         }
-        
-        Self::append_chat_message(&formatted_message);
+        DEFAULT_SERVER_URL.to_string()
     }
 
-    fn add_pending_message(message: &str, pending_messages: &Rc<RefCell<HashMap<String, web_sys::Element>>>) {
-        let window = web_sys::window().unwrap();
-        let document = window.document().unwrap();
-        
-        if let Some(chat_messages) = document.get_element_by_id("chatMessages") {
-            let message_div = document.create_element("div").unwrap();
-            
-            // Format like server messages
-            let timestamp = Self::format_current_timestamp();
-            let username = document.get_element_by_id("userDisplay")
-                .and_then(|el| el.text_content())
-                .unwrap_or_else(|| "Unknown".to_string());
-            
-            let formatted_text = format!("[{}] {}: {} [SENDING...]", timestamp, username, message);
-            message_div.set_text_content(Some(&formatted_text));
-            message_div.set_attribute("style", "opacity: 0.6; font-style: italic").unwrap();
-            
-            let _ = chat_messages.append_child(&message_div);
-            chat_messages.set_scroll_top(chat_messages.scroll_height());
-            
-            // Store in pending
-            let message_key = message.to_lowercase().trim().to_string();
-            pending_messages.borrow_mut().insert(message_key, message_div);
+    fn set_connection_state(connection_state: &Rc<Cell<ConnectionState>>, events: &EventBus, state: ConnectionState, attempt: u32, delay_ms: Option<u32>) {
+        // Repeat notifications only for reconnecting, which carries a new attempt number
+        if connection_state.replace(state) == state && state != ConnectionState::Reconnecting {
+            return;
         }
+
+        events.emit(&ClientEvent::ConnectionState { state, attempt, delay_ms });
     }
 
-    fn append_chat_message(message: &str) {
-        let window = web_sys::window().unwrap();
-        let document = window.document().unwrap();
-        
-        if let Some(chat_messages) = document.get_element_by_id("chatMessages") {
-            let message_div = document.create_element("div").unwrap();
-            message_div.set_text_content(Some(message));
-            
-            let _ = chat_messages.append_child(&message_div);
-            chat_messages.set_scroll_top(chat_messages.scroll_height());
-            
-            // Limit messages to 100 - use child_element_count for counting
-            while chat_messages.child_element_count() > 100 {
-                if let Some(first_child) = chat_messages.first_child() {
-                    let _ = chat_messages.remove_child(&first_child);
-                }
-            }
+    fn set_mode(mode_cell: &Rc<Cell<ClientMode>>, events: &EventBus, mode: ClientMode) {
+        if mode_cell.replace(mode) != mode {
+            console_log!("🧭 Client mode: {}", mode.as_str());
+            events.emit(&ClientEvent::ModeChanged { mode });
         }
     }
 
-    fn format_timestamp(timestamp_value: &serde_json::Value) -> String {
+    fn handle_pong(seq: u32, client_time: f64, server_time: f64, latency: &Rc<RefCell<LatencyTracker>>, clock: &Rc<RefCell<ClockSync>>, events: &EventBus) {
+        let now = js_sys::Date::now();
+        clock.borrow_mut().add_sample(client_time, server_time, now);
+
+        let Some(rtt) = latency.borrow_mut().record_pong(seq, now) else {
+            return; // Late pong for a ping already counted as lost
+        };
+
+        let stats = latency.borrow().stats();
+        events.emit(&ClientEvent::Latency { rtt_ms: rtt.round(), stats });
+    }
+
+    fn handle_chat_message(chat_msg: ChatMessage, pending_messages: &Rc<RefCell<PendingChats>>, clock: &Rc<RefCell<ClockSync>>, events: &EventBus) {
+        // Confirm our pending line only when our own echo carries its nonce
+        let nonce = chat_msg.nonce
+            .filter(|nonce| pending_messages.borrow_mut().confirm(nonce, &chat_msg.username));
+
+        events.emit(&ClientEvent::Chat {
+            timestamp: Self::format_timestamp(&chat_msg.timestamp, clock),
+            username: chat_msg.username,
+            message: chat_msg.message,
+            status: ChatStatus::Sent,
+            nonce,
+        });
+    }
+
+    fn format_timestamp(timestamp_value: &serde_json::Value, clock: &Rc<RefCell<ClockSync>>) -> String {
         if let Some(timestamp_str) = timestamp_value.as_str() {
             if let Ok(parsed) = chrono::DateTime::parse_from_rfc3339(timestamp_str) {
-                let utc_time = parsed.with_timezone(&chrono::Utc);
-                return format!("{:02}:{:02}:{:02}.{:02}", 
-                    utc_time.hour(), 
-                    utc_time.minute(), 
-                    utc_time.second(),
-                    utc_time.nanosecond() / 10_000_000
-                );
+                return Self::format_time_of_day(&parsed.with_timezone(&chrono::Utc));
             }
         }
-        Self::format_current_timestamp()
+        // Epoch milliseconds
+        if let Some(utc_time) = timestamp_value.as_f64().and_then(|ms| chrono::DateTime::from_timestamp_millis(ms as i64)) {
+            return Self::format_time_of_day(&utc_time);
+        }
+        Self::format_current_timestamp(clock)
+    }
+
+    // Local stamps use the estimated server clock so optimistic and confirmed lines agree
+    fn format_current_timestamp(clock: &Rc<RefCell<ClockSync>>) -> String {
+        let now_ms = Self::server_now(clock);
+        let now = chrono::DateTime::from_timestamp_millis(now_ms as i64).unwrap_or_else(chrono::Utc::now);
+        Self::format_time_of_day(&now)
     }
 
-    fn format_current_timestamp() -> String {
-        let now = chrono::Utc::now();
+    fn format_time_of_day(utc_time: &chrono::DateTime<chrono::Utc>) -> String {
         format!("{:02}:{:02}:{:02}.{:02}", 
-            now.hour(), 
-            now.minute(), 
-            now.second(),
-            now.nanosecond() / 10_000_000
+            utc_time.hour(), 
+            utc_time.minute(), 
+            utc_time.second(),
+            utc_time.nanosecond() / 10_000_000
         )
     }
 
+    fn server_now(clock: &Rc<RefCell<ClockSync>>) -> f64 {
+        clock.borrow().server_now(js_sys::Date::now())
+    }
+
     fn is_websocket_connected(&self) -> bool {
-        if let Some(ref websocket) = self.websocket {
+        Self::is_socket_open(&self.websocket)
+    }
+
+    fn is_socket_open(websocket: &Rc<RefCell<Option<WebSocket>>>) -> bool {
+        if let Some(ref websocket) = *websocket.borrow() {
             websocket.ready_state() == WebSocket::OPEN
         } else {
             false
@@ -854,6 +1956,13 @@ impl IronVeinClient {
     }
 }
 
+impl Drop for IronVeinClient {
+    fn drop(&mut self) {
+        // JS `free()` lands here; make sure nothing keeps calling into freed state
+        self.teardown();
+    }
+}
+
 #[wasm_bindgen(start)]
 pub fn main() {
     console_log!("🚀 IronVein Rust Game Engine initialized!");
@@ -0,0 +1,52 @@
+// DOM event listeners owned from Rust, so they can be removed again instead
+// of being leaked with `Closure::forget`.
+
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{Event, EventTarget};
+
+pub type Listener = Closure<dyn FnMut(Event)>;
+
+// Listeners on a single target, at most one per event name
+#[derive(Default)]
+pub struct Listeners {
+    target: Option<EventTarget>,
+    handlers: HashMap<&'static str, Listener>,
+}
+
+impl Listeners {
+    // Adds a listener, replacing any previous one for the same event.
+    // Moving to a different target detaches everything from the old one first.
+    pub fn attach(&mut self, target: &EventTarget, event: &'static str, handler: impl FnMut(Event) + 'static) -> Result<(), JsValue> {
+        if self.target.as_ref() != Some(target) {
+            self.detach_all();
+            self.target = Some(target.clone());
+        }
+
+        if let Some(previous) = self.handlers.remove(event) {
+            let _ = target.remove_event_listener_with_callback(event, previous.as_ref().unchecked_ref());
+        }
+
+        let listener = Closure::wrap(Box::new(handler) as Box<dyn FnMut(Event)>);
+        target.add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())?;
+        self.handlers.insert(event, listener);
+        Ok(())
+    }
+
+    pub fn detach_all(&mut self) {
+        if let Some(target) = self.target.take() {
+            for (event, listener) in self.handlers.drain() {
+                let _ = target.remove_event_listener_with_callback(event, listener.as_ref().unchecked_ref());
+            }
+        }
+        self.handlers.clear();
+    }
+}
+
+impl Drop for Listeners {
+    fn drop(&mut self) {
+        self.detach_all();
+    }
+}
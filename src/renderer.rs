@@ -0,0 +1,489 @@
+// Canvas renderer. Terrain and grid lines are drawn once to an offscreen
+// canvas and blitted each frame; frames are skipped entirely when neither
+// the game state nor the camera changed since the last one. Units are drawn
+// from the sprite atlas once it has loaded, and as plain squares until then.
+// The minimap and the hover tooltip are drawn last, over everything, in
+// canvas pixels.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement};
+
+use crate::game::camera::Camera;
+use crate::game::grid::{self, MapSize, CELL_SIZE};
+use crate::game::hover::Hover;
+use crate::game::minimap::{Minimap, MinimapView};
+use crate::game::motion::Motion;
+use crate::game::protocol::Player;
+use crate::game::sprites::{Animator, Frame};
+use crate::game::state::GameState;
+use crate::game::status::{self, FloatingNumber};
+use crate::game::terrain::{Terrain, TileMap};
+use crate::sprite_sheet::SpriteSheet;
+
+// Longest side of the offscreen layer; larger maps are cached at reduced scale
+const STATIC_LAYER_MAX_PX: f64 = 4096.0;
+
+// Low-health outline pulse period, and how far floating numbers rise
+const WARNING_PULSE_MS: f64 = 800.0;
+const FLOATING_RISE_PX: f64 = 14.0;
+
+// Offscreen copy of everything that only changes with the map
+struct StaticLayer {
+    canvas: HtmlCanvasElement,
+    // One pixel per cell, scaled up for the minimap
+    minimap: HtmlCanvasElement,
+    static_revision: u64,
+}
+
+pub struct Renderer {
+    context: CanvasRenderingContext2d,
+    static_layer: Option<StaticLayer>,
+    // State revision, camera and minimap of the last drawn frame
+    last_frame: Option<(u64, Camera, Minimap)>,
+    // Shared with the client, which fills it in once the atlas has loaded
+    sprites: Rc<RefCell<Option<SpriteSheet>>>,
+    animator: Animator,
+}
+
+impl Renderer {
+    pub fn new(context: CanvasRenderingContext2d, sprites: Rc<RefCell<Option<SpriteSheet>>>) -> Self {
+        Self {
+            context,
+            static_layer: None,
+            last_frame: None,
+            sprites,
+            animator: Animator::default(),
+        }
+    }
+
+    // Forces the next frame to redraw
+    pub fn request_redraw(&mut self) {
+        self.last_frame = None;
+    }
+
+    // Forces the next frame to redraw, rebuilding the cached layer as well
+    pub fn invalidate(&mut self) {
+        self.static_layer = None;
+        self.last_frame = None;
+    }
+
+    // Draws a frame if anything changed or something on the map is animating.
+    // Returns whether it drew.
+    pub fn render(
+        &mut self,
+        state: &GameState,
+        camera: &Camera,
+        motion: &Motion,
+        hover: &Hover,
+        minimap: &Minimap,
+        now: f64,
+    ) -> Result<bool, JsValue> {
+        let frame = (state.revision, camera.clone(), *minimap);
+        let sprites = self.sprites.borrow();
+        // Fresh low-health warnings and sprite animations keep playing while
+        // shown, and a resting pointer needs a frame once its tooltip is due
+        let animating = motion.is_animating(now)
+            || hover.is_dwelling(now)
+            || state.players.values().any(|player| motion.status.is_pulsing(player, now))
+            || sprites.as_ref().is_some_and(|sheet| sheet.atlas.is_animated() && self.animator.is_animating(now));
+        if !animating && self.last_frame.as_ref() == Some(&frame) {
+            return Ok(false);
+        }
+
+        let stale = self.static_layer.as_ref()
+            .is_none_or(|layer| layer.static_revision != state.static_revision);
+        if stale {
+            self.static_layer = Some(Self::build_static_layer(state)?);
+        }
+
+        let context = &self.context;
+
+        // Clear canvas
+        context.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)?;
+        context.clear_rect(0.0, 0.0, camera.viewport_width, camera.viewport_height);
+
+        // Everything below is drawn in world pixels
+        let [a, b, c, d, e, f] = camera.transform();
+        context.set_transform(a, b, c, d, e, f)?;
+
+        // Blit terrain and grid in one call
+        if let Some(ref layer) = self.static_layer {
+            context.set_image_smoothing_enabled(false);
+            context.draw_image_with_html_canvas_element_and_dw_and_dh(
+                &layer.canvas,
+                0.0,
+                0.0,
+                state.map.world_width(),
+                state.map.world_height(),
+            )?;
+        }
+
+        // Hovered cell and route preview under the players
+        Self::draw_highlight(context, hover);
+        Self::draw_route(context, hover);
+
+        // Draw players
+        Self::draw_players(context, state, motion, sprites.as_ref(), &mut self.animator, now)?;
+        Self::draw_floating_numbers(context, motion, now)?;
+
+        context.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)?;
+        if let (Some(view), Some(ref layer)) = (minimap.layout(camera), &self.static_layer) {
+            Self::draw_minimap(context, &view, &layer.minimap, state, camera, motion, now)?;
+        }
+        if let Some(lines) = hover.tooltip(state, now) {
+            Self::draw_tooltip(context, camera, hover, &lines)?;
+        }
+        // Mid-animation frames are never reused; the one after it settles is kept
+        self.last_frame = if animating { None } else { Some(frame) };
+        Ok(true)
+    }
+
+    fn build_static_layer(state: &GameState) -> Result<StaticLayer, JsValue> {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+
+        let (world_width, world_height) = (state.map.world_width(), state.map.world_height());
+        let scale = (STATIC_LAYER_MAX_PX / world_width.max(world_height)).min(1.0);
+        canvas.set_width((world_width * scale).ceil() as u32);
+        canvas.set_height((world_height * scale).ceil() as u32);
+
+        let context = canvas
+            .get_context("2d")?
+            .ok_or("2d context not found")?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+        context.scale(scale, scale)?;
+
+        Self::draw_terrain(&context, &state.terrain);
+        Self::draw_grid(&context, &state.map);
+
+        let minimap = Self::build_minimap_layer(&document, &state.terrain)?;
+        Ok(StaticLayer { canvas, minimap, static_revision: state.static_revision })
+    }
+
+    // Terrain at one pixel per cell, with iron veins brightened so resources
+    // stand out at minimap scale
+    fn build_minimap_layer(document: &Document, terrain: &TileMap) -> Result<HtmlCanvasElement, JsValue> {
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_width(terrain.size.width.max(1));
+        canvas.set_height(terrain.size.height.max(1));
+        let context = canvas
+            .get_context("2d")?
+            .ok_or("2d context not found")?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+
+        context.set_fill_style_str(Self::terrain_color(Terrain::Plains));
+        context.fill_rect(0.0, 0.0, terrain.size.width as f64, terrain.size.height as f64);
+        for (x, y, kind) in terrain.features() {
+            let color = match kind {
+                Terrain::IronVein => "#e67e22",
+                kind => Self::terrain_color(kind),
+            };
+            context.set_fill_style_str(color);
+            context.fill_rect(x as f64, y as f64, 1.0, 1.0);
+        }
+        Ok(canvas)
+    }
+
+    fn draw_terrain(context: &CanvasRenderingContext2d, terrain: &TileMap) {
+        // Plains everywhere, then the other kinds on top
+        context.set_fill_style_str(Self::terrain_color(Terrain::Plains));
+        context.fill_rect(0.0, 0.0, terrain.size.world_width(), terrain.size.world_height());
+
+        for (x, y, kind) in terrain.features() {
+            let (cell_x, cell_y) = grid::cell_origin(x, y);
+            context.set_fill_style_str(Self::terrain_color(kind));
+            context.fill_rect(cell_x, cell_y, CELL_SIZE as f64, CELL_SIZE as f64);
+        }
+    }
+
+    fn terrain_color(kind: Terrain) -> &'static str {
+        match kind {
+            Terrain::Plains => "#1e2419",
+            Terrain::Rock => "#4a4744",
+            Terrain::Water => "#1c3d5a",
+            Terrain::IronVein => "#8b4a2b",
+        }
+    }
+
+    fn draw_grid(context: &CanvasRenderingContext2d, map: &MapSize) {
+        context.set_stroke_style_str("#333");
+        context.set_line_width(0.5);
+
+        // One path for every line, stroked once
+        context.begin_path();
+        for i in 0..=map.width {
+            let pos = (i * CELL_SIZE) as f64;
+            context.move_to(pos, 0.0);
+            context.line_to(pos, map.world_height());
+        }
+        for i in 0..=map.height {
+            let pos = (i * CELL_SIZE) as f64;
+            context.move_to(0.0, pos);
+            context.line_to(map.world_width(), pos);
+        }
+        context.stroke();
+    }
+
+    fn draw_highlight(context: &CanvasRenderingContext2d, hover: &Hover) {
+        let Some((x, y)) = hover.cell else {
+            return;
+        };
+        let (cell_x, cell_y) = grid::cell_origin(x, y);
+        let size = CELL_SIZE as f64;
+        context.set_fill_style_str("rgba(236, 240, 241, 0.15)");
+        context.fill_rect(cell_x, cell_y, size, size);
+        context.set_stroke_style_str("rgba(236, 240, 241, 0.6)");
+        context.set_line_width(1.0);
+        context.stroke_rect(cell_x + 0.5, cell_y + 0.5, size - 1.0, size - 1.0);
+    }
+
+    // Dotted line along the planned path; the target is outlined orange when
+    // reachable and crossed out in red when not
+    fn draw_route(context: &CanvasRenderingContext2d, hover: &Hover) {
+        let (Some((target_x, target_y)), Some(from)) = (hover.cell, hover.from) else {
+            return;
+        };
+        let (cell_x, cell_y) = grid::cell_origin(target_x, target_y);
+        let size = CELL_SIZE as f64;
+
+        match hover.path {
+            Some(ref path) if !path.is_empty() => {
+                context.set_stroke_style_str("#f39c12");
+                context.set_line_width(2.0);
+                let _ = context.set_line_dash(&js_sys::Array::of2(&3.0.into(), &3.0.into()));
+                context.begin_path();
+                let (start_x, start_y) = grid::cell_center(from.0, from.1);
+                context.move_to(start_x, start_y);
+                for &(x, y) in path {
+                    let (x, y) = grid::cell_center(x, y);
+                    context.line_to(x, y);
+                }
+                context.stroke();
+                let _ = context.set_line_dash(&js_sys::Array::new());
+                context.stroke_rect(cell_x + 1.0, cell_y + 1.0, size - 2.0, size - 2.0);
+            }
+            Some(_) => {} // hovering our own cell
+            None => {
+                context.set_stroke_style_str("#e74c3c");
+                context.set_line_width(2.0);
+                context.begin_path();
+                context.move_to(cell_x + 3.0, cell_y + 3.0);
+                context.line_to(cell_x + size - 3.0, cell_y + size - 3.0);
+                context.move_to(cell_x + size - 3.0, cell_y + 3.0);
+                context.line_to(cell_x + 3.0, cell_y + size - 3.0);
+                context.stroke();
+            }
+        }
+    }
+
+    fn draw_players(
+        context: &CanvasRenderingContext2d,
+        state: &GameState,
+        motion: &Motion,
+        sprites: Option<&SpriteSheet>,
+        animator: &mut Animator,
+        now: f64,
+    ) -> Result<(), JsValue> {
+        // Sprite frame for a unit drawn at (x, y) this frame
+        let mut sprite = |player: &Player, x: f64, y: f64| {
+            let pose = animator.update(&player.username, (x, y), motion.status.last_hit(&player.username), now);
+            let skin = if player.username == state.username { "self" } else { "other" };
+            sprites.and_then(|sheet| sheet.atlas.frame(skin, &pose, now).map(|frame| (sheet, *frame)))
+        };
+
+        // Departed players fade out where they were last drawn
+        for departed in motion.lifecycle.departed() {
+            context.set_global_alpha(departed.opacity(now));
+            let frame = sprite(&departed.player, departed.x, departed.y);
+            Self::draw_unit(context, &departed.player, &state.username, departed.x, departed.y, frame)?;
+        }
+        context.set_global_alpha(1.0);
+
+        for player in state.players.values() {
+            let (x, y) = motion.position(&player.username, now)
+                .unwrap_or((player.x as f64, player.y as f64));
+            let frame = sprite(player, x, y);
+            Self::draw_unit(context, player, &state.username, x, y, frame)?;
+            Self::draw_status(context, player, x, y, motion.status.is_pulsing(player, now), now);
+        }
+
+        animator.retain(|username| {
+            state.players.contains_key(username)
+                || motion.lifecycle.departed().iter().any(|departed| departed.player.username == username)
+        });
+        Ok(())
+    }
+
+    // One unit at a (fractional) cell position: its sprite frame scaled to fit
+    // the cell and standing on its bottom edge, or a colored square without one
+    fn draw_unit(
+        context: &CanvasRenderingContext2d,
+        player: &Player,
+        local: &str,
+        cell_x: f64,
+        cell_y: f64,
+        sprite: Option<(&SpriteSheet, Frame)>,
+    ) -> Result<(), JsValue> {
+        let (x, y) = (cell_x * CELL_SIZE as f64, cell_y * CELL_SIZE as f64);
+        let size = CELL_SIZE as f64;
+
+        if let Some((sheet, frame)) = sprite {
+            let scale = size / frame.w.max(frame.h);
+            let (width, height) = (frame.w * scale, frame.h * scale);
+            context.set_image_smoothing_enabled(false);
+            context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                &sheet.image,
+                frame.x,
+                frame.y,
+                frame.w,
+                frame.h,
+                x + (size - width) / 2.0,
+                y + size - height,
+                width,
+                height,
+            )?;
+        } else {
+            if player.username == local {
+                // Draw self in green
+                context.set_fill_style_str("#4CAF50");
+            } else {
+                // Draw others in red
+                context.set_fill_style_str("#F44336");
+            }
+
+            context.fill_rect(x + 2.0, y + 2.0, (CELL_SIZE - 4) as f64, (CELL_SIZE - 4) as f64);
+        }
+
+        // Draw username
+        context.set_fill_style_str("white");
+        context.set_font("10px Arial");
+        context.fill_text(&player.username, x + 2.0, y + CELL_SIZE as f64 - 2.0)?;
+        Ok(())
+    }
+
+    // Health bar along the top of the unit, a pip when it carries resources,
+    // and a red outline when health is low, pulsing for a while after it changed
+    fn draw_status(context: &CanvasRenderingContext2d, player: &Player, cell_x: f64, cell_y: f64, pulsing: bool, now: f64) {
+        let (x, y) = (cell_x * CELL_SIZE as f64, cell_y * CELL_SIZE as f64);
+        let width = (CELL_SIZE - 4) as f64;
+        let fraction = status::health_fraction(player.health);
+
+        context.set_fill_style_str("#111");
+        context.fill_rect(x + 2.0, y, width, 2.0);
+        context.set_fill_style_str(status::health_color(fraction));
+        context.fill_rect(x + 2.0, y, width * fraction, 2.0);
+
+        if player.resources > 0 {
+            context.set_fill_style_str("#e67e22");
+            context.fill_rect(x + CELL_SIZE as f64 - 6.0, y + 3.0, 3.0, 3.0);
+        }
+
+        if status::shows_warning(player.health) {
+            if pulsing {
+                let pulse = (now / WARNING_PULSE_MS * std::f64::consts::TAU).sin() * 0.5 + 0.5;
+                context.set_global_alpha(0.3 + 0.7 * pulse);
+            }
+            context.set_stroke_style_str("#e74c3c");
+            context.set_line_width(1.5);
+            context.stroke_rect(x + 1.0, y + 1.0, (CELL_SIZE - 2) as f64, (CELL_SIZE - 2) as f64);
+            context.set_global_alpha(1.0);
+        }
+    }
+
+    // Damage (red) and healing (green) rising and fading above the unit,
+    // following it while it is still on the map
+    fn draw_floating_numbers(context: &CanvasRenderingContext2d, motion: &Motion, now: f64) -> Result<(), JsValue> {
+        context.set_font("bold 10px Arial");
+        context.set_text_align("center");
+        for number in motion.status.floating() {
+            let FloatingNumber { ref username, delta, x, y, .. } = *number;
+            let progress = number.progress(now);
+            let (cell_x, cell_y) = motion.position(username, now).unwrap_or((x as f64, y as f64));
+            let text_x = (cell_x + 0.5) * CELL_SIZE as f64;
+            let text_y = cell_y * CELL_SIZE as f64 - 2.0 - FLOATING_RISE_PX * progress;
+
+            context.set_global_alpha(1.0 - progress);
+            context.set_fill_style_str(if delta < 0 { "#e74c3c" } else { "#2ecc71" });
+            context.fill_text(&number.text(), text_x, text_y)?;
+        }
+        context.set_global_alpha(1.0);
+        context.set_text_align("start");
+        Ok(())
+    }
+
+    // Whole-map overview: terrain and resources, every unit as a dot, and the
+    // part of the world the camera shows
+    fn draw_minimap(
+        context: &CanvasRenderingContext2d,
+        view: &MinimapView,
+        layer: &HtmlCanvasElement,
+        state: &GameState,
+        camera: &Camera,
+        motion: &Motion,
+        now: f64,
+    ) -> Result<(), JsValue> {
+        let rect = view.rect;
+        context.set_fill_style_str("rgba(0, 0, 0, 0.6)");
+        context.fill_rect(rect.x - 2.0, rect.y - 2.0, rect.width + 4.0, rect.height + 4.0);
+        context.set_image_smoothing_enabled(false);
+        context.draw_image_with_html_canvas_element_and_dw_and_dh(layer, rect.x, rect.y, rect.width, rect.height)?;
+
+        let dot = (CELL_SIZE as f64 * view.scale).max(3.0);
+        for player in state.players.values() {
+            let (cell_x, cell_y) = motion.position(&player.username, now)
+                .unwrap_or((player.x as f64, player.y as f64));
+            let (x, y) = view.world_to_screen((cell_x + 0.5) * CELL_SIZE as f64, (cell_y + 0.5) * CELL_SIZE as f64);
+            context.set_fill_style_str(if player.username == state.username { "#4CAF50" } else { "#F44336" });
+            context.fill_rect(x - dot / 2.0, y - dot / 2.0, dot, dot);
+        }
+
+        let viewport = view.viewport(camera);
+        context.set_stroke_style_str("white");
+        context.set_line_width(1.0);
+        context.stroke_rect(viewport.x + 0.5, viewport.y + 0.5, (viewport.width - 1.0).max(1.0), (viewport.height - 1.0).max(1.0));
+        Ok(())
+    }
+
+    // Cell details beside the hovered cell, kept inside the canvas
+    fn draw_tooltip(context: &CanvasRenderingContext2d, camera: &Camera, hover: &Hover, lines: &[String]) -> Result<(), JsValue> {
+        const PADDING: f64 = 6.0;
+        const LINE_HEIGHT: f64 = 14.0;
+        let Some((x, y)) = hover.cell else {
+            return Ok(());
+        };
+
+        context.set_font("12px 'Segoe UI', Tahoma, sans-serif");
+        let mut text_width: f64 = 0.0;
+        for line in lines {
+            text_width = text_width.max(context.measure_text(line)?.width());
+        }
+        let width = text_width + PADDING * 2.0;
+        let height = LINE_HEIGHT * lines.len() as f64 + PADDING * 2.0;
+
+        // Right of the cell, or left of it when that would leave the canvas
+        let (cell_x, cell_y) = grid::cell_origin(x, y);
+        let (right, top) = camera.world_to_screen(cell_x + CELL_SIZE as f64, cell_y);
+        let (left, _) = camera.world_to_screen(cell_x, cell_y);
+        let box_x = if right + 8.0 + width <= camera.viewport_width { right + 8.0 } else { left - 8.0 - width };
+        let box_x = box_x.clamp(0.0, (camera.viewport_width - width).max(0.0));
+        let box_y = top.clamp(0.0, (camera.viewport_height - height).max(0.0));
+
+        context.set_fill_style_str("rgba(26, 37, 47, 0.92)");
+        context.fill_rect(box_x, box_y, width, height);
+        context.set_stroke_style_str("#5d6d7e");
+        context.set_line_width(1.0);
+        context.stroke_rect(box_x + 0.5, box_y + 0.5, width - 1.0, height - 1.0);
+
+        context.set_fill_style_str("#ecf0f1");
+        context.set_text_baseline("top");
+        for (i, line) in lines.iter().enumerate() {
+            context.fill_text(line, box_x + PADDING, box_y + PADDING + i as f64 * LINE_HEIGHT)?;
+        }
+        context.set_text_baseline("alphabetic");
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+// Unit sprite atlas: the texture image plus its frame metadata, fetched by the
+// page through `IronVeinClient::load_sprite_atlas`.
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{HtmlImageElement, Response};
+
+use crate::game::sprites::SpriteAtlas;
+
+pub struct SpriteSheet {
+    pub image: HtmlImageElement,
+    pub atlas: SpriteAtlas,
+}
+
+impl SpriteSheet {
+    pub async fn load(image_url: &str, metadata_url: &str) -> Result<Self, JsValue> {
+        let window = web_sys::window().ok_or("No window")?;
+
+        let response: Response = JsFuture::from(window.fetch_with_str(metadata_url)).await?.dyn_into()?;
+        if !response.ok() {
+            return Err(format!("Sprite metadata request failed with status {}", response.status()).into());
+        }
+        let text = JsFuture::from(response.text()?).await?
+            .as_string()
+            .ok_or("Sprite metadata is not text")?;
+        let atlas = SpriteAtlas::from_json(&text)?;
+
+        let image = HtmlImageElement::new()?;
+        image.set_src(image_url);
+        JsFuture::from(image.decode()).await
+            .map_err(|_| JsValue::from_str(&format!("Sprite image {} failed to load", image_url)))?;
+
+        if !atlas.fits(image.natural_width() as f64, image.natural_height() as f64) {
+            return Err("Sprite frames lie outside the atlas image".into());
+        }
+        Ok(Self { image, atlas })
+    }
+}